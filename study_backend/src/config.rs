@@ -0,0 +1,177 @@
+use std::{collections::HashMap, error::Error, net::IpAddr, path::PathBuf};
+
+use clap::Parser;
+use serde::Deserialize;
+use study_shared_types::AdviserMode;
+use warp::http::Uri;
+
+/// Command line flags. Every flag overrides the matching setting of the config file.
+#[derive(Debug, Parser)]
+#[command(about = "Receives study sessions, results and steps from the study frontend.")]
+pub struct Args {
+    /// TOML file with the server settings
+    #[arg(long, short)]
+    pub config: Option<PathBuf>,
+    /// address to listen on
+    #[arg(long)]
+    pub listen_addr: Option<IpAddr>,
+    /// port to listen on
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// origin allowed to call the backend, e.g. `https://study.example.org`. May be repeated,
+    /// `*` allows any origin.
+    #[arg(long = "allow-origin")]
+    pub allowed_origins: Vec<String>,
+    /// largest accepted result submission in bytes
+    #[arg(long)]
+    pub body_limit: Option<u64>,
+    /// largest accepted step batch in bytes
+    #[arg(long)]
+    pub steps_body_limit: Option<u64>,
+}
+
+/// Server settings, read from the config file and command line flags.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen_addr: IpAddr,
+    pub port: u16,
+    pub allowed_origins: Vec<String>,
+    pub body_limit: u64,
+    pub steps_body_limit: u64,
+    /// participants per adviser mode, read from `TARGET_LEAST_LIMITING`, `TARGET_NEXT_MOVE` and
+    /// `TARGET_NONE`. A mode without a target is never considered full.
+    #[serde(skip)]
+    pub assignment_targets: HashMap<AdviserMode, u32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen_addr: IpAddr::from([0, 0, 0, 0]),
+            port: 3030,
+            allowed_origins: vec!["*".to_owned()],
+            body_limit: 1024 * 16,
+            steps_body_limit: 1024 * 256,
+            assignment_targets: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let args = Args::parse();
+
+        let mut config = match &args.config {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+                toml::from_str(&text)
+                    .map_err(|e| format!("Could not parse {}: {}", path.display(), e))?
+            }
+            None => Config::default(),
+        };
+
+        if let Some(listen_addr) = args.listen_addr {
+            config.listen_addr = listen_addr;
+        }
+        if let Some(port) = args.port {
+            config.port = port;
+        }
+        if !args.allowed_origins.is_empty() {
+            config.allowed_origins = args.allowed_origins;
+        }
+        if let Some(body_limit) = args.body_limit {
+            config.body_limit = body_limit;
+        }
+        if let Some(steps_body_limit) = args.steps_body_limit {
+            config.steps_body_limit = steps_body_limit;
+        }
+
+        config.assignment_targets = parse_targets(|var| std::env::var(var).ok())?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// warp panics on malformed origins, so they are checked up front.
+    fn validate(&self) -> Result<(), String> {
+        if self.allowed_origins.is_empty() {
+            return Err("allowed_origins must not be empty, use \"*\" to allow any".to_owned());
+        }
+        for origin in self.allowed_origins.iter().filter(|o| *o != "*") {
+            let valid = match origin.parse::<Uri>() {
+                Ok(uri) => {
+                    uri.scheme().is_some()
+                        && uri.authority().is_some()
+                        && matches!(uri.path(), "" | "/")
+                }
+                Err(_) => false,
+            };
+            if !valid || origin.ends_with('/') {
+                return Err(format!(
+                    "invalid origin {:?}, expected scheme://host[:port]",
+                    origin
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+}
+
+/// Reads the assignment targets once at startup, so that a misconfigured target stops the
+/// server instead of failing every new session.
+fn parse_targets(
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<HashMap<AdviserMode, u32>, String> {
+    let mut targets = HashMap::new();
+    for mode in AdviserMode::ALL {
+        let var = match mode {
+            AdviserMode::LeastLimiting => "TARGET_LEAST_LIMITING",
+            AdviserMode::NextMove => "TARGET_NEXT_MOVE",
+            AdviserMode::None => "TARGET_NONE",
+        };
+        if let Some(value) = lookup(var) {
+            let target = value.trim().parse::<u32>().map_err(|_| {
+                format!(
+                    "{} must be a non-negative whole number, got {:?}",
+                    var, value
+                )
+            })?;
+            targets.insert(mode, target);
+        }
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_are_parsed_once() {
+        let targets = parse_targets(|var| match var {
+            "TARGET_NEXT_MOVE" => Some("40".to_owned()),
+            "TARGET_NONE" => Some(" 0 ".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(targets.get(&AdviserMode::NextMove), Some(&40));
+        assert_eq!(targets.get(&AdviserMode::None), Some(&0));
+        assert_eq!(targets.get(&AdviserMode::LeastLimiting), None);
+    }
+
+    #[test]
+    fn malformed_targets_are_rejected() {
+        for value in ["-1", "ten", ""] {
+            let err =
+                parse_targets(|var| (var == "TARGET_LEAST_LIMITING").then(|| value.to_owned()))
+                    .unwrap_err();
+            assert!(err.contains("TARGET_LEAST_LIMITING"), "{}", err);
+        }
+    }
+}
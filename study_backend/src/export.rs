@@ -0,0 +1,451 @@
+use std::{collections::HashMap, sync::Arc};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use study_shared_types::{AdviserMode, InteractState};
+use warp::http::{self, header};
+
+use crate::{
+    error_status,
+    storage::{SessionRecord, Storage},
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+/// Query string of the export endpoints, e.g. `?format=json&from=2022-10-01&adviser_mode=NextMove`.
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// first day to include, `YYYY-MM-DD` in UTC
+    pub from: Option<String>,
+    /// last day to include, `YYYY-MM-DD` in UTC
+    pub to: Option<String>,
+    pub adviser_mode: Option<AdviserMode>,
+    pub pilot: Option<bool>,
+}
+
+/// One row of the results export. The first columns are named like the ones
+/// `study_results/parse_results.py` joins on, so the export can be used as `db_game_results.csv`.
+#[derive(Debug, Serialize)]
+struct ResultRow {
+    participant_id: i32,
+    adviser_mode: AdviserMode,
+    steps_taken: u32,
+    safety_violated: u32,
+    human_burgers: u32,
+    robot_burgers: u32,
+    pilot: bool,
+    submitted_at: u64,
+    schema_version: u32,
+    study_id: String,
+    client_build: String,
+    client_started_at: u64,
+    client_submitted_at: u64,
+    flags: String,
+}
+
+#[derive(Debug, Serialize)]
+struct StepRow {
+    participant_id: i32,
+    adviser_mode: AdviserMode,
+    pilot: bool,
+    step: u32,
+    timestamp_ms: u64,
+    human_move: String,
+    robot_move: String,
+    human_x: u32,
+    human_y: u32,
+    robot_x: u32,
+    robot_y: u32,
+    human_interact: InteractState,
+    robot_interact: InteractState,
+    observation: String,
+    /// JSON encoded, the graph state contains commas
+    game_state: String,
+    safety_guards: String,
+    fairness_guards: String,
+    violated: bool,
+}
+
+/// Days since 1970-01-01 for a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses `YYYY-MM-DD` into unix seconds at midnight UTC.
+fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    u64::try_from(days).ok().map(|d| d * SECONDS_PER_DAY)
+}
+
+struct RecordFilter {
+    /// unix seconds, inclusive
+    from: Option<u64>,
+    /// unix seconds, exclusive
+    until: Option<u64>,
+    adviser_mode: Option<AdviserMode>,
+    pilot: Option<bool>,
+}
+
+impl RecordFilter {
+    fn new(query: &ExportQuery) -> Result<Self, String> {
+        let date = |d: &Option<String>| match d {
+            Some(d) => parse_date(d)
+                .map(Some)
+                .ok_or_else(|| format!("invalid date {:?}, expected YYYY-MM-DD", d)),
+            None => Ok(None),
+        };
+        Ok(RecordFilter {
+            from: date(&query.from)?,
+            until: date(&query.to)?.map(|t| t + SECONDS_PER_DAY),
+            adviser_mode: query.adviser_mode,
+            pilot: query.pilot,
+        })
+    }
+
+    fn matches(&self, adviser_mode: AdviserMode, pilot: bool, at: u64) -> bool {
+        self.from.is_none_or(|from| at >= from)
+            && self.until.is_none_or(|until| at < until)
+            && self.adviser_mode.is_none_or(|mode| mode == adviser_mode)
+            && self.pilot.is_none_or(|p| p == pilot)
+    }
+}
+
+/// Compares in constant time so that the token cannot be guessed byte by byte.
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// The export token is read from `EXPORT_TOKEN`. Without it, exports are disabled.
+fn authorized(authorization: Option<&str>, export_token: Option<&str>) -> bool {
+    match (
+        authorization.and_then(|a| a.strip_prefix("Bearer ")),
+        export_token,
+    ) {
+        (Some(given), Some(expected)) => token_matches(given, expected),
+        _ => false,
+    }
+}
+
+fn to_csv<T: Serialize>(rows: &[T]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row).map_err(|e| e.to_string())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+fn reply_rows<T: Serialize>(rows: &[T], format: ExportFormat) -> Box<dyn warp::Reply> {
+    match format {
+        ExportFormat::Json => Box::new(warp::reply::json(&rows)),
+        ExportFormat::Csv => match to_csv(rows) {
+            Ok(csv) => Box::new(warp::reply::with_header(
+                csv,
+                header::CONTENT_TYPE,
+                "text/csv; charset=utf-8",
+            )),
+            Err(e) => {
+                error!("Could not write csv: {}", e);
+                Box::new(http::StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+    }
+}
+
+/// Checks authorization and parses the filter, or returns the reply to send instead.
+fn prepare(
+    query: &ExportQuery,
+    authorization: Option<&str>,
+    export_token: Option<&str>,
+) -> Result<RecordFilter, Box<dyn warp::Reply>> {
+    if !authorized(authorization, export_token) {
+        warn!("Rejected an unauthorized export.");
+        return Err(Box::new(http::StatusCode::UNAUTHORIZED));
+    }
+    RecordFilter::new(query).map_err(|e| -> Box<dyn warp::Reply> {
+        Box::new(warp::reply::with_status(e, http::StatusCode::BAD_REQUEST))
+    })
+}
+
+async fn sessions_by_participant(
+    storage: &Arc<dyn Storage>,
+) -> Result<HashMap<i32, SessionRecord>, Box<dyn warp::Reply>> {
+    match storage.sessions().await {
+        Ok(sessions) => Ok(sessions
+            .into_iter()
+            .map(|s| (s.participant_id, s))
+            .collect()),
+        Err(e) => {
+            error!("Could not read sessions: {}", e);
+            Err(Box::new(error_status(&e)))
+        }
+    }
+}
+
+pub async fn export_results(
+    query: ExportQuery,
+    authorization: Option<String>,
+    export_token: Option<String>,
+    storage: Arc<dyn Storage>,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let filter = match prepare(&query, authorization.as_deref(), export_token.as_deref()) {
+        Ok(f) => f,
+        Err(reply) => return Ok(reply),
+    };
+    let sessions = match sessions_by_participant(&storage).await {
+        Ok(s) => s,
+        Err(reply) => return Ok(reply),
+    };
+    let results = match storage.results().await {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not read results: {}", e);
+            return Ok(Box::new(error_status(&e)));
+        }
+    };
+
+    let rows: Vec<ResultRow> = results
+        .into_iter()
+        .filter_map(|r| {
+            // results from before sessions were issued count as part of the real study
+            let pilot = sessions.get(&r.participant_id).is_some_and(|s| s.pilot);
+            if !filter.matches(r.adviser_mode, pilot, r.submitted_at) {
+                return None;
+            }
+            Some(ResultRow {
+                participant_id: r.participant_id,
+                adviser_mode: r.adviser_mode,
+                steps_taken: r.steps_taken,
+                safety_violated: r.safety_violated,
+                human_burgers: r.human_burgers,
+                robot_burgers: r.robot_burgers,
+                pilot,
+                submitted_at: r.submitted_at,
+                schema_version: r.schema_version,
+                study_id: r.study_id,
+                client_build: r.client_build,
+                client_started_at: r.client_started_at,
+                client_submitted_at: r.client_submitted_at,
+                flags: r.flags.join(","),
+            })
+        })
+        .collect();
+
+    info!("Exporting {} results.", rows.len());
+    Ok(reply_rows(&rows, query.format))
+}
+
+pub async fn export_steps(
+    query: ExportQuery,
+    authorization: Option<String>,
+    export_token: Option<String>,
+    storage: Arc<dyn Storage>,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let filter = match prepare(&query, authorization.as_deref(), export_token.as_deref()) {
+        Ok(f) => f,
+        Err(reply) => return Ok(reply),
+    };
+    let sessions = match sessions_by_participant(&storage).await {
+        Ok(s) => s,
+        Err(reply) => return Ok(reply),
+    };
+    let steps = match storage.steps().await {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not read steps: {}", e);
+            return Ok(Box::new(error_status(&e)));
+        }
+    };
+
+    let rows: Vec<StepRow> = steps
+        .into_iter()
+        .filter_map(|s| {
+            // steps only carry the time since the game started, so they are dated by their session
+            let session = sessions.get(&s.participant_id)?;
+            if !filter.matches(session.adviser_mode, session.pilot, session.started_at) {
+                return None;
+            }
+            let event = s.event;
+            Some(StepRow {
+                participant_id: s.participant_id,
+                adviser_mode: session.adviser_mode,
+                pilot: session.pilot,
+                step: event.step,
+                timestamp_ms: event.timestamp_ms,
+                human_move: event.human_move,
+                robot_move: event.robot_move,
+                human_x: event.human_pos.0,
+                human_y: event.human_pos.1,
+                robot_x: event.robot_pos.0,
+                robot_y: event.robot_pos.1,
+                human_interact: event.human_interact,
+                robot_interact: event.robot_interact,
+                observation: event.observation,
+                game_state: serde_json::to_string(&event.game_state).unwrap_or_default(),
+                safety_guards: event.safety_guards.join(","),
+                fairness_guards: event.fairness_guards.join(","),
+                violated: event.violated,
+            })
+        })
+        .collect();
+
+    info!("Exporting {} steps.", rows.len());
+    Ok(reply_rows(&rows, query.format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_parsed_as_utc_midnight() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2022-10-01"), Some(1_664_582_400));
+        assert_eq!(parse_date("2024-02-29"), Some(1_709_164_800));
+        assert_eq!(parse_date("2000-03-01"), Some(951_868_800));
+    }
+
+    #[test]
+    fn invalid_dates_are_rejected() {
+        for date in [
+            "2022-13-01",
+            "2022-00-10",
+            "2022-04-31",
+            "2023-02-29",
+            "1900-02-29",
+            "1969-12-31",
+            "2022-10",
+            "01.10.2022",
+            "",
+        ] {
+            assert_eq!(parse_date(date), None, "{}", date);
+        }
+    }
+
+    #[test]
+    fn the_last_day_is_included() {
+        let query = ExportQuery {
+            format: ExportFormat::Csv,
+            from: Some("2022-10-01".to_owned()),
+            to: Some("2022-10-01".to_owned()),
+            adviser_mode: Some(AdviserMode::NextMove),
+            pilot: Some(false),
+        };
+        let filter = RecordFilter::new(&query).unwrap();
+        let day = parse_date("2022-10-01").unwrap();
+        assert!(filter.matches(AdviserMode::NextMove, false, day));
+        assert!(filter.matches(AdviserMode::NextMove, false, day + SECONDS_PER_DAY - 1));
+        assert!(!filter.matches(AdviserMode::NextMove, false, day + SECONDS_PER_DAY));
+        assert!(!filter.matches(AdviserMode::NextMove, false, day - 1));
+        assert!(!filter.matches(AdviserMode::None, false, day));
+        assert!(!filter.matches(AdviserMode::NextMove, true, day));
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("", "secret"));
+
+        assert!(authorized(Some("Bearer secret"), Some("secret")));
+        assert!(!authorized(Some("secret"), Some("secret")));
+        assert!(!authorized(Some("Basic secret"), Some("secret")));
+        assert!(!authorized(None, Some("secret")));
+        // without a configured token, nothing is exported
+        assert!(!authorized(Some("Bearer "), None));
+    }
+
+    #[test]
+    fn csv_starts_with_the_analysis_columns() {
+        let row = StepRow {
+            participant_id: 123456,
+            adviser_mode: AdviserMode::LeastLimiting,
+            pilot: false,
+            step: 3,
+            timestamp_ms: 1200,
+            human_move: "Down".to_owned(),
+            robot_move: "Idle".to_owned(),
+            human_x: 1,
+            human_y: 2,
+            robot_x: 3,
+            robot_y: 4,
+            human_interact: InteractState::In,
+            robot_interact: InteractState::No,
+            observation: "00010".to_owned(),
+            game_state: serde_json::to_string(&("21i", "0", "3")).unwrap(),
+            safety_guards: "1XXXX|X0XXX".to_owned(),
+            fairness_guards: String::new(),
+            violated: false,
+        };
+        let csv = to_csv(&[row]).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("participant_id,adviser_mode,pilot,step,"));
+        // the graph state contains commas and is quoted
+        assert_eq!(
+            lines.next().unwrap(),
+            r#"123456,LeastLimiting,false,3,1200,Down,Idle,1,2,3,4,In,No,00010,"[""21i"",""0"",""3""]",1XXXX|X0XXX,,false"#
+        );
+
+        let result = ResultRow {
+            participant_id: 123456,
+            adviser_mode: AdviserMode::None,
+            steps_taken: 250,
+            safety_violated: 2,
+            human_burgers: 4,
+            robot_burgers: 5,
+            pilot: true,
+            submitted_at: 1_664_582_400,
+            schema_version: 2,
+            study_id: "study".to_owned(),
+            client_build: "dev".to_owned(),
+            client_started_at: 0,
+            client_submitted_at: 0,
+            flags: "no_steps,short_session".to_owned(),
+        };
+        let csv = to_csv(&[result]).unwrap();
+        assert!(csv.starts_with(
+            "participant_id,adviser_mode,steps_taken,safety_violated,human_burgers,robot_burgers,"
+        ));
+        assert!(csv.ends_with(",\"no_steps,short_session\"\n"), "{}", csv);
+    }
+}
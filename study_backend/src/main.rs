@@ -1,116 +1,471 @@
+mod config;
+mod export;
+mod metrics;
+mod storage;
+mod validation;
+
 //use dotenv::dotenv;
-use log::{error, info};
-use mysql_async::{prelude::*, OptsBuilder};
-use study_shared_types::{AdviserMode, GameResults};
+use std::{collections::HashMap, sync::Arc};
+
+use log::{error, info, warn};
+use metrics::Metrics;
+use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
+use serde::Deserialize;
+use storage::{InsertOutcome, SessionRecord, Storage, StorageError};
+use study_shared_types::{AdviserMode, ResultSubmission, SessionInfo, StepBatch, SubmissionError};
+use tokio::sync::Mutex;
+use validation::{Rejection, Violation};
 use warp::{
     http::{self},
     Filter,
 };
 
-fn db_url() -> OptsBuilder {
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
-    let database_name = std::env::var("DATABASE_NAME").expect("DATABASE_NAME must be set.");
-    let database_port = std::env::var("DATABASE_PORT")
-        .expect("DATABASE_PORT must be set.")
-        .parse::<u16>()
-        .expect("DATABASE_PORT is not a u16.");
-    let user = std::env::var("DATABASE_USER").expect("DATABASE_USER must be set.");
-    let pass = std::env::var("DATABASE_PASS").expect("DATABASE_PASS must be set.");
-
-    OptsBuilder::default()
-        .ip_or_hostname(database_url)
-        .tcp_port(database_port)
-        .user(Some(user))
-        .pass(Some(pass))
-        .db_name(Some(database_name))
+/// Block randomization: every block contains each mode that has not reached its target exactly
+/// once, so a new block only starts when all modes in it have been handed out. Within the
+/// current block, the next mode is drawn uniformly from the modes not yet assigned.
+fn choose_adviser_mode(
+    counts: &HashMap<AdviserMode, u32>,
+    targets: &HashMap<AdviserMode, u32>,
+) -> AdviserMode {
+    let count = |mode: &AdviserMode| counts.get(mode).copied().unwrap_or(0);
+    let open: Vec<AdviserMode> = AdviserMode::ALL
+        .iter()
+        .filter(|mode| match targets.get(mode) {
+            Some(&target) => count(mode) < target,
+            None => true,
+        })
+        .copied()
+        .collect();
+    // once every target is met, keep balancing across all modes
+    let candidates = if open.is_empty() {
+        AdviserMode::ALL.to_vec()
+    } else {
+        open
+    };
+
+    let block_min = candidates.iter().map(count).min().unwrap_or(0);
+    let remaining: Vec<AdviserMode> = candidates
+        .into_iter()
+        .filter(|mode| count(mode) == block_min)
+        .collect();
+
+    *remaining
+        .choose(&mut rand::thread_rng())
+        .expect("There is always at least one mode left in a block.")
 }
 
-async fn insert_user_data(game_result: GameResults) -> Result<impl warp::Reply, warp::Rejection> {
-    info!("Received a result: {:?}", game_result);
+/// How often we draw a new participant id if the drawn one is already taken.
+const PARTICIPANT_ID_ATTEMPTS: usize = 16;
+const SESSION_TOKEN_LEN: usize = 32;
 
-    let pool = mysql_async::Pool::new(db_url());
-    let mut conn = match pool.get_conn().await {
+fn new_session_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SESSION_TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+fn error_status(e: &StorageError) -> http::StatusCode {
+    match e {
+        StorageError::UnknownParticipant => http::StatusCode::NOT_FOUND,
+        StorageError::WrongToken => http::StatusCode::FORBIDDEN,
+        StorageError::AlreadyCompleted => http::StatusCode::CONFLICT,
+        StorageError::Unavailable(_) => http::StatusCode::SERVICE_UNAVAILABLE,
+        StorageError::DuplicateParticipant | StorageError::Backend(_) => {
+            http::StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionQuery {
+    /// set by the frontend for pilot runs, e.g. from `?pilot=true` on the study page
+    #[serde(default)]
+    pilot: bool,
+}
+
+async fn assign_session(
+    query: SessionQuery,
+    storage: Arc<dyn Storage>,
+    assignment_lock: Arc<Mutex<()>>,
+    targets: Arc<HashMap<AdviserMode, u32>>,
+    metrics: Arc<Metrics>,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    // serialize assignments so that two concurrent sessions do not see the same counts
+    let _guard = assignment_lock.lock().await;
+
+    let counts = match storage.session_counts().await {
         Ok(c) => c,
         Err(e) => {
-            error!("Could not connect: {}", e);
-            return Ok(http::StatusCode::INTERNAL_SERVER_ERROR);
+            error!("Could not count sessions: {}", e);
+            return Ok(Box::new(error_status(&e)));
         }
     };
+    let adviser_mode = choose_adviser_mode(&counts, &targets);
 
-    let query = r"INSERT INTO study_data (participant_id, adviser_mode, steps_taken, safety_violated, human_burgers, robot_burgers)
-      VALUES (:participant_id, :adviser_mode, :steps_taken, :safety_violated, :human_burgers, :robot_burgers)"
-        .with(params! {
-            "participant_id" => game_result.participant_id,
-            "adviser_mode" => AdviserMode::from_num(game_result.adviser_mode).to_string(),
-            "steps_taken" => game_result.steps_taken,
-            "safety_violated" => game_result.safety_violated,
-            "human_burgers" => game_result.human_burgers,
-            "robot_burgers" => game_result.robot_burgers,
-        });
-
-    // insert game result data
-    if let Err(e) = query.ignore(&mut conn).await {
-        error!("Could not insert: {}", e);
-        return Ok(http::StatusCode::INTERNAL_SERVER_ERROR);
+    // participant ids are short enough to be typed into the questionnaire, so they can collide.
+    // the storage rejects duplicates, we just draw again.
+    let mut session = SessionRecord {
+        participant_id: 0,
+        session_token: new_session_token(),
+        adviser_mode,
+        started_at: storage::unix_now(),
+        pilot: query.pilot,
+    };
+    let mut inserted = false;
+    for _ in 0..PARTICIPANT_ID_ATTEMPTS {
+        session.participant_id = rand::thread_rng().gen_range(100000..999999);
+        match storage.insert_session(&session).await {
+            Ok(()) => {
+                inserted = true;
+                break;
+            }
+            Err(StorageError::DuplicateParticipant) => {
+                info!(
+                    "Participant id {} already taken, drawing again.",
+                    session.participant_id
+                );
+            }
+            Err(e) => {
+                error!("Could not insert: {}", e);
+                metrics.insert_failed("session");
+                return Ok(Box::new(error_status(&e)));
+            }
+        }
     }
+    if !inserted {
+        error!("Could not find a free participant id.");
+        metrics.insert_failed("session");
+        return Ok(Box::new(http::StatusCode::SERVICE_UNAVAILABLE));
+    }
+    metrics.session_started(adviser_mode);
 
-    if let Err(e) = conn.disconnect().await {
-        error!("Could not disconnect: {}", e);
-        return Ok(http::StatusCode::INTERNAL_SERVER_ERROR);
+    info!(
+        "Started session for participant {} with adviser mode {:?}.",
+        session.participant_id, adviser_mode
+    );
+    Ok(Box::new(warp::reply::with_status(
+        warp::reply::json(&SessionInfo {
+            participant_id: session.participant_id,
+            session_token: session.session_token,
+            adviser_mode,
+        }),
+        http::StatusCode::CREATED,
+    )))
+}
+
+/// Results sent again with the same key are answered like the first time instead of with `409`.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+fn unprocessable(rejection: Rejection) -> Box<dyn warp::Reply> {
+    error!("Rejected an implausible result: {}", rejection.message);
+    Box::new(warp::reply::with_status(
+        warp::reply::json(&rejection),
+        http::StatusCode::UNPROCESSABLE_ENTITY,
+    ))
+}
+
+async fn insert_user_data(
+    idempotency_key: Option<String>,
+    body: warp::hyper::body::Bytes,
+    storage: Arc<dyn Storage>,
+    metrics: Arc<Metrics>,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    // parsed by hand instead of with warp::body::json so that older clients can be upgraded
+    // and rejections carry a readable reason
+    let submission = match std::str::from_utf8(&body)
+        .map_err(|e| SubmissionError::Malformed(e.to_string()))
+        .and_then(ResultSubmission::parse)
+    {
+        Ok(s) => s,
+        Err(SubmissionError::InvalidAdviserMode(m)) => {
+            let rejection = Rejection::new(
+                Violation::InvalidAdviserMode,
+                format!("adviser mode {} does not exist", m),
+            );
+            return Ok(unprocessable(rejection));
+        }
+        Err(e) => {
+            error!("Rejected a result: {}", e);
+            return Ok(Box::new(warp::reply::with_status(
+                e.to_string(),
+                http::StatusCode::BAD_REQUEST,
+            )));
+        }
+    };
+    info!("Received a result: {:?}", submission);
+
+    let flags = match validation::validate(&submission) {
+        Ok(f) => f,
+        Err(rejection) => return Ok(unprocessable(rejection)),
+    };
+    if !flags.is_empty() {
+        warn!(
+            "Flagged the result of participant {}: {:?}",
+            submission.result.participant_id, flags
+        );
     }
-    if let Err(e) = pool.disconnect().await {
-        error!("Could not connect: {}", e);
-        return Ok(http::StatusCode::INTERNAL_SERVER_ERROR);
+
+    match storage
+        .insert_result(&submission, &flags, idempotency_key.as_deref())
+        .await
+    {
+        Ok(InsertOutcome::Inserted) => {
+            info!("Result insertion succeeded.");
+            metrics.result_submitted(submission.result.adviser_mode);
+        }
+        Ok(InsertOutcome::Repeated) => {
+            info!("Result was already stored, nothing to do.");
+        }
+        Err(e) => {
+            error!("Could not insert: {}", e);
+            metrics.insert_failed("result");
+            return Ok(Box::new(error_status(&e)));
+        }
+    }
+
+    Ok(Box::new(http::StatusCode::CREATED))
+}
+
+async fn insert_step_data(
+    step_batch: StepBatch,
+    storage: Arc<dyn Storage>,
+    metrics: Arc<Metrics>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    info!(
+        "Received {} steps of participant {}.",
+        step_batch.events.len(),
+        step_batch.participant_id
+    );
+
+    if let Err(e) = storage.insert_steps(&step_batch).await {
+        error!("Could not insert: {}", e);
+        metrics.insert_failed("steps");
+        return Ok(error_status(&e));
     }
 
-    info!("Result insertion succeeded.");
+    info!("Step insertion succeeded.");
     Ok(http::StatusCode::CREATED)
 }
 
+async fn readiness(storage: Arc<dyn Storage>) -> Result<impl warp::Reply, warp::Rejection> {
+    match storage.ping().await {
+        Ok(()) => Ok(warp::reply::with_status(
+            "ready".to_owned(),
+            http::StatusCode::OK,
+        )),
+        Err(e) => {
+            error!("Readiness check failed: {}", e);
+            Ok(warp::reply::with_status(
+                e.to_string(),
+                http::StatusCode::SERVICE_UNAVAILABLE,
+            ))
+        }
+    }
+}
+
 #[tokio::main]
 pub async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     //dotenv().expect("Could not find .env file!");
     pretty_env_logger::init();
 
-    let pool = mysql_async::Pool::new(db_url());
-    let mut conn = pool.get_conn().await?;
+    let config = config::Config::load()?;
+    info!("Starting with {:?}", config);
 
-    // make sure the table exists
-    r"CREATE TABLE if not exists study_data (
-        participant_id int primary key,
-        adviser_mode ENUM('LeastLimiting', 'NextMove', 'None'),
-        steps_taken int not null,
-        safety_violated int not null,
-        human_burgers int not null,
-        robot_burgers int not null        
-    )"
-    .ignore(&mut conn)
-    .await?;
+    let storage = storage::from_env()?;
 
-    conn.disconnect().await?;
-    pool.disconnect().await?;
+    // make sure the tables exist
+    storage.create_schema().await?;
+    let with_storage = warp::any().map(move || storage.clone());
+
+    let metrics = Arc::new(Metrics::new()?);
+    let with_metrics = {
+        let metrics = metrics.clone();
+        warp::any().map(move || metrics.clone())
+    };
 
     // CORS settings
     let cors = warp::cors()
-        .allow_headers(vec!["content-type"])
-        .allow_methods(vec!["POST", "GET"])
-        .allow_any_origin();
+        .allow_headers(vec!["content-type", IDEMPOTENCY_KEY_HEADER])
+        .allow_methods(vec!["POST", "GET"]);
+    let cors = if config.allows_any_origin() {
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(config.allowed_origins.iter().map(String::as_str))
+    };
 
     // POST database insert
     let post_user_data = warp::post()
         .and(warp::path("data"))
-        .and(warp::body::content_length_limit(1024 * 16))
-        .and(warp::body::json())
+        .and(warp::header::optional::<String>(IDEMPOTENCY_KEY_HEADER))
+        .and(warp::body::content_length_limit(config.body_limit))
+        .and(warp::body::bytes())
+        .and(with_storage.clone())
+        .and(with_metrics.clone())
         .and_then(insert_user_data);
 
+    // POST step trajectory batch
+    let post_step_data = warp::post()
+        .and(warp::path("steps"))
+        .and(warp::body::content_length_limit(config.steps_body_limit))
+        .and(warp::body::json())
+        .and(with_storage.clone())
+        .and(with_metrics.clone())
+        .and_then(insert_step_data);
+
+    // POST new session with adviser mode assignment
+    let assignment_lock = Arc::new(Mutex::new(()));
+    let assignment_targets = Arc::new(config.assignment_targets.clone());
+    let post_session = warp::post()
+        .and(warp::path("session"))
+        .and(warp::query::<SessionQuery>())
+        .and(with_storage.clone())
+        .and(warp::any().map(move || assignment_lock.clone()))
+        .and(warp::any().map(move || assignment_targets.clone()))
+        .and(with_metrics.clone())
+        .and_then(assign_session);
+
+    // GET token protected exports for analysis
+    let export_token = std::env::var("EXPORT_TOKEN").ok();
+    if export_token.is_none() {
+        warn!("EXPORT_TOKEN is not set, exports are disabled.");
+    }
+    let with_export_auth = warp::header::optional::<String>("authorization")
+        .and(warp::any().map(move || export_token.clone()));
+    let get_export_results = warp::get()
+        .and(warp::path!("export" / "results"))
+        .and(warp::query::<export::ExportQuery>())
+        .and(with_export_auth.clone())
+        .and(with_storage.clone())
+        .and_then(export::export_results);
+    let get_export_steps = warp::get()
+        .and(warp::path!("export" / "steps"))
+        .and(warp::query::<export::ExportQuery>())
+        .and(with_export_auth)
+        .and(with_storage.clone())
+        .and_then(export::export_steps);
+
     // GET stupid health check
     let health = warp::get()
-        .and(warp::path("health"))
+        .and(warp::path!("health"))
         .map(|| "study backend is online!");
 
-    let routes = post_user_data.or(health).with(cors);
+    // GET readiness, fails while the storage cannot be reached
+    let health_ready = warp::get()
+        .and(warp::path!("health" / "ready"))
+        .and(with_storage.clone())
+        .and_then(readiness);
+
+    // GET prometheus metrics
+    let get_metrics = warp::get()
+        .and(warp::path!("metrics"))
+        .and(with_metrics)
+        .and_then(metrics::metrics);
+
+    let routes = post_user_data
+        .or(post_step_data)
+        .or(post_session)
+        .or(get_export_results)
+        .or(get_export_steps)
+        .or(health)
+        .or(health_ready)
+        .or(get_metrics)
+        .with(cors)
+        .with(warp::log::custom(move |info| metrics.observe_request(info)));
 
-    warp::serve(routes).run(([0, 0, 0, 0], 3030)).await;
+    warp::serve(routes)
+        .run((config.listen_addr, config.port))
+        .await;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::sqlite::SqliteStorage;
+
+    fn counts(least_limiting: u32, next_move: u32, none: u32) -> HashMap<AdviserMode, u32> {
+        HashMap::from([
+            (AdviserMode::LeastLimiting, least_limiting),
+            (AdviserMode::NextMove, next_move),
+            (AdviserMode::None, none),
+        ])
+    }
+
+    /// Every mode drawn in 300 assignments from the same counts.
+    fn drawn(
+        counts: &HashMap<AdviserMode, u32>,
+        targets: &HashMap<AdviserMode, u32>,
+    ) -> Vec<AdviserMode> {
+        let mut modes: Vec<AdviserMode> = (0..300)
+            .map(|_| choose_adviser_mode(counts, targets))
+            .collect();
+        modes.sort_by_key(AdviserMode::to_num);
+        modes.dedup();
+        modes
+    }
+
+    #[test]
+    fn block_is_completed_first() {
+        let modes = drawn(&counts(3, 2, 3), &HashMap::new());
+        assert_eq!(modes, vec![AdviserMode::NextMove]);
+        let modes = drawn(&counts(3, 2, 2), &HashMap::new());
+        assert_eq!(modes, vec![AdviserMode::NextMove, AdviserMode::None]);
+    }
+
+    #[test]
+    fn ties_draw_every_mode() {
+        let modes = drawn(&counts(4, 4, 4), &HashMap::new());
+        assert_eq!(modes, AdviserMode::ALL.to_vec());
+    }
+
+    #[test]
+    fn modes_at_their_target_are_skipped() {
+        let targets = HashMap::from([(AdviserMode::LeastLimiting, 2)]);
+        let modes = drawn(&counts(2, 5, 5), &targets);
+        assert_eq!(modes, vec![AdviserMode::NextMove, AdviserMode::None]);
+    }
+
+    #[test]
+    fn balancing_goes_on_once_all_targets_are_met() {
+        let targets = HashMap::from([
+            (AdviserMode::LeastLimiting, 2),
+            (AdviserMode::NextMove, 2),
+            (AdviserMode::None, 2),
+        ]);
+        let modes = drawn(&counts(3, 2, 3), &targets);
+        assert_eq!(modes, vec![AdviserMode::NextMove]);
+    }
+
+    #[tokio::test]
+    async fn pilot_sessions_do_not_count() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        storage.create_schema().await.unwrap();
+        let sessions = [
+            (1, AdviserMode::LeastLimiting, false),
+            (2, AdviserMode::NextMove, false),
+            (3, AdviserMode::None, true),
+            (4, AdviserMode::None, true),
+        ];
+        for (participant_id, adviser_mode, pilot) in sessions {
+            let session = SessionRecord {
+                participant_id,
+                session_token: new_session_token(),
+                adviser_mode,
+                started_at: storage::unix_now(),
+                pilot,
+            };
+            storage.insert_session(&session).await.unwrap();
+        }
+
+        let counts = storage.session_counts().await.unwrap();
+        assert_eq!(counts.get(&AdviserMode::None).copied().unwrap_or(0), 0);
+        assert_eq!(
+            drawn(&counts, &HashMap::new()),
+            vec![AdviserMode::None],
+            "pilot sessions must not fill the block"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use log::error;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use study_shared_types::AdviserMode;
+use warp::{http::StatusCode, log::Info};
+
+/// Routes are labelled by name instead of by path, so that scans for random paths do not
+/// create a new time series each.
+const ROUTES: [(&str, &str); 8] = [
+    ("/data", "data"),
+    ("/steps", "steps"),
+    ("/session", "session"),
+    ("/export/results", "export_results"),
+    ("/export/steps", "export_steps"),
+    ("/health", "health"),
+    ("/health/ready", "health_ready"),
+    ("/metrics", "metrics"),
+];
+
+/// Everything exposed on `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    insert_failures: IntCounterVec,
+    sessions: IntCounterVec,
+    submissions: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("study_backend".to_owned()), None)?;
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status."),
+            &["route", "method", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time to answer HTTP requests by route.",
+            ),
+            &["route"],
+        )?;
+        let insert_failures = IntCounterVec::new(
+            Opts::new(
+                "insert_failures_total",
+                "Sessions, results and steps that could not be stored.",
+            ),
+            &["kind"],
+        )?;
+        let sessions = IntCounterVec::new(
+            Opts::new("sessions_total", "Sessions started by adviser mode."),
+            &["adviser_mode"],
+        )?;
+        let submissions = IntCounterVec::new(
+            Opts::new("submissions_total", "Results stored by adviser mode."),
+            &["adviser_mode"],
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(insert_failures.clone()))?;
+        registry.register(Box::new(sessions.clone()))?;
+        registry.register(Box::new(submissions.clone()))?;
+
+        // export zeros for every mode, so that rates work from the first session on
+        for mode in AdviserMode::ALL {
+            sessions.with_label_values(&[&mode.to_string()]);
+            submissions.with_label_values(&[&mode.to_string()]);
+        }
+
+        Ok(Metrics {
+            registry,
+            requests,
+            request_duration,
+            insert_failures,
+            sessions,
+            submissions,
+        })
+    }
+
+    /// Called for every answered request, see `warp::log::custom`.
+    pub fn observe_request(&self, info: Info) {
+        let route = ROUTES
+            .iter()
+            .find(|(path, _)| *path == info.path().trim_end_matches('/'))
+            .map_or("other", |(_, name)| *name);
+        self.requests
+            .with_label_values(&[route, info.method().as_str(), info.status().as_str()])
+            .inc();
+        self.request_duration
+            .with_label_values(&[route])
+            .observe(info.elapsed().as_secs_f64());
+    }
+
+    /// `kind` is one of `session`, `result` or `steps`.
+    pub fn insert_failed(&self, kind: &str) {
+        self.insert_failures.with_label_values(&[kind]).inc();
+    }
+
+    pub fn session_started(&self, adviser_mode: AdviserMode) {
+        self.sessions
+            .with_label_values(&[&adviser_mode.to_string()])
+            .inc();
+    }
+
+    pub fn result_submitted(&self, adviser_mode: AdviserMode) {
+        self.submissions
+            .with_label_values(&[&adviser_mode.to_string()])
+            .inc();
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn render(&self) -> Result<String, String> {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buf).map_err(|e| e.to_string())
+    }
+}
+
+pub async fn metrics(metrics: Arc<Metrics>) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    match metrics.render() {
+        Ok(text) => Ok(Box::new(warp::reply::with_header(
+            text,
+            warp::http::header::CONTENT_TYPE,
+            TextEncoder::new().format_type().to_owned(),
+        ))),
+        Err(e) => {
+            error!("Could not render metrics: {}", e);
+            Ok(Box::new(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use study_shared_types::{AdviserMode, ResultSubmission, StepBatch};
+use tokio::sync::Mutex;
+
+use crate::validation::Flag;
+
+use super::{InsertOutcome, ResultRecord, SessionRecord, StepRecord, Storage, StorageError};
+
+const SESSIONS_FILE: &str = "sessions.jsonl";
+const RESULTS_FILE: &str = "results.jsonl";
+const STEPS_FILE: &str = "steps.jsonl";
+
+/// Append-only JSON lines files in a directory, one file per record type. Sessions and
+/// completions are kept in memory so that checks do not need to rescan the files.
+pub struct JsonlStorage {
+    dir: PathBuf,
+    index: Mutex<SessionIndex>,
+}
+
+#[derive(Default)]
+struct SessionIndex {
+    sessions: HashMap<i32, SessionRecord>,
+    /// idempotency keys of the stored results
+    completed: HashMap<i32, Option<String>>,
+    /// participant and step of every stored step, so resent batches are not stored twice
+    steps: HashSet<(i32, u32)>,
+}
+
+fn read_lines<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, StorageError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut records = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line)?);
+    }
+    Ok(records)
+}
+
+fn append_lines<T: Serialize>(path: &Path, records: &[T]) -> Result<(), StorageError> {
+    let mut buf = String::new();
+    for record in records {
+        buf.push_str(&serde_json::to_string(record)?);
+        buf.push('\n');
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(buf.as_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+impl JsonlStorage {
+    pub fn open(dir: &str) -> Result<Self, StorageError> {
+        Ok(JsonlStorage {
+            dir: PathBuf::from(dir),
+            index: Mutex::new(SessionIndex::default()),
+        })
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+}
+
+#[async_trait]
+impl Storage for JsonlStorage {
+    async fn create_schema(&self) -> Result<(), StorageError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut index = self.index.lock().await;
+        let sessions: Vec<SessionRecord> = read_lines(&self.path(SESSIONS_FILE))?;
+        let results: Vec<ResultRecord> = read_lines(&self.path(RESULTS_FILE))?;
+        let steps: Vec<StepRecord> = read_lines(&self.path(STEPS_FILE))?;
+        index.sessions = sessions
+            .into_iter()
+            .map(|s| (s.participant_id, s))
+            .collect();
+        index.completed = results
+            .into_iter()
+            .map(|r| (r.participant_id, r.idempotency_key))
+            .collect();
+        index.steps = steps
+            .into_iter()
+            .map(|s| (s.participant_id, s.event.step))
+            .collect();
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        let metadata = fs::metadata(&self.dir)?;
+        if !metadata.is_dir() || metadata.permissions().readonly() {
+            return Err(StorageError::Backend(format!(
+                "{} is not a writable directory",
+                self.dir.display()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn session_counts(&self) -> Result<HashMap<AdviserMode, u32>, StorageError> {
+        let index = self.index.lock().await;
+        let mut counts = HashMap::new();
+        for session in index.sessions.values().filter(|s| !s.pilot) {
+            *counts.entry(session.adviser_mode).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    async fn insert_session(&self, session: &SessionRecord) -> Result<(), StorageError> {
+        let mut index = self.index.lock().await;
+        if index.sessions.contains_key(&session.participant_id) {
+            return Err(StorageError::DuplicateParticipant);
+        }
+        append_lines(&self.path(SESSIONS_FILE), &[session])?;
+        index
+            .sessions
+            .insert(session.participant_id, session.clone());
+        Ok(())
+    }
+
+    async fn insert_result(
+        &self,
+        submission: &ResultSubmission,
+        flags: &[Flag],
+        idempotency_key: Option<&str>,
+    ) -> Result<InsertOutcome, StorageError> {
+        let result = &submission.result;
+        // results are only accepted for sessions we handed out, and only once
+        let mut index = self.index.lock().await;
+        match index.sessions.get(&result.participant_id) {
+            None => return Err(StorageError::UnknownParticipant),
+            Some(s) if s.session_token != result.session_token => {
+                return Err(StorageError::WrongToken)
+            }
+            Some(_) => (),
+        }
+        if let Some(stored_key) = index.completed.get(&result.participant_id) {
+            return match (stored_key.as_deref(), idempotency_key) {
+                (Some(stored), Some(given)) if stored == given => Ok(InsertOutcome::Repeated),
+                _ => Err(StorageError::AlreadyCompleted),
+            };
+        }
+
+        append_lines(
+            &self.path(RESULTS_FILE),
+            &[ResultRecord::new(submission, flags, idempotency_key)],
+        )?;
+        index
+            .completed
+            .insert(result.participant_id, idempotency_key.map(str::to_owned));
+        Ok(InsertOutcome::Inserted)
+    }
+
+    async fn insert_steps(&self, batch: &StepBatch) -> Result<(), StorageError> {
+        // the last batch may arrive after the result, so completed sessions are fine here
+        let mut index = self.index.lock().await;
+        match index.sessions.get(&batch.participant_id) {
+            None => return Err(StorageError::UnknownParticipant),
+            Some(s) if s.session_token != batch.session_token => {
+                return Err(StorageError::WrongToken)
+            }
+            Some(_) => (),
+        }
+
+        // steps stored by an earlier copy of the batch are skipped
+        let mut new_steps = HashSet::new();
+        let records: Vec<StepRecord> = batch
+            .events
+            .iter()
+            .filter(|event| {
+                let key = (batch.participant_id, event.step);
+                !index.steps.contains(&key) && new_steps.insert(key)
+            })
+            .map(|event| StepRecord {
+                participant_id: batch.participant_id,
+                event: event.clone(),
+            })
+            .collect();
+        append_lines(&self.path(STEPS_FILE), &records)?;
+        index.steps.extend(new_steps);
+        Ok(())
+    }
+
+    async fn sessions(&self) -> Result<Vec<SessionRecord>, StorageError> {
+        let index = self.index.lock().await;
+        Ok(index.sessions.values().cloned().collect())
+    }
+
+    async fn results(&self) -> Result<Vec<ResultRecord>, StorageError> {
+        let _index = self.index.lock().await;
+        read_lines(&self.path(RESULTS_FILE))
+    }
+
+    async fn steps(&self) -> Result<Vec<StepRecord>, StorageError> {
+        let _index = self.index.lock().await;
+        read_lines(&self.path(STEPS_FILE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::fixtures;
+
+    #[tokio::test]
+    async fn results_are_stored_once() {
+        let dir = fixtures::temp_dir("results");
+        let storage = JsonlStorage::open(dir.to_str().unwrap()).unwrap();
+        fixtures::check_result_idempotency(&storage).await;
+
+        // the stored keys are remembered across restarts
+        let storage = JsonlStorage::open(dir.to_str().unwrap()).unwrap();
+        storage.create_schema().await.unwrap();
+        let submission = fixtures::submission(100001, fixtures::TOKEN);
+        let outcome = storage.insert_result(&submission, &[], Some("a")).await;
+        assert!(matches!(outcome, Ok(InsertOutcome::Repeated)));
+        let outcome = storage.insert_result(&submission, &[], Some("b")).await;
+        assert!(matches!(outcome, Err(StorageError::AlreadyCompleted)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resent_steps_are_stored_once() {
+        let dir = fixtures::temp_dir("resent_steps");
+        let storage = JsonlStorage::open(dir.to_str().unwrap()).unwrap();
+        storage.create_schema().await.unwrap();
+        storage
+            .insert_session(&fixtures::session(100001))
+            .await
+            .unwrap();
+
+        storage
+            .insert_steps(&fixtures::steps(100001, 0..10))
+            .await
+            .unwrap();
+        storage
+            .insert_steps(&fixtures::steps(100001, 0..10))
+            .await
+            .unwrap();
+
+        // the stored steps are remembered across restarts
+        let storage = JsonlStorage::open(dir.to_str().unwrap()).unwrap();
+        storage.create_schema().await.unwrap();
+        storage
+            .insert_steps(&fixtures::steps(100001, 5..15))
+            .await
+            .unwrap();
+
+        let steps: Vec<u32> = storage
+            .steps()
+            .await
+            .unwrap()
+            .iter()
+            .map(|s| s.event.step)
+            .collect();
+        assert_eq!(steps, (0..15).collect::<Vec<_>>());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use study_shared_types::{AdviserMode, ResultSubmission, StepBatch, StepEvent};
+
+use crate::validation::Flag;
+
+pub mod jsonl;
+pub mod mysql;
+pub mod sqlite;
+
+#[derive(Debug)]
+pub enum StorageError {
+    DuplicateParticipant,
+    UnknownParticipant,
+    WrongToken,
+    AlreadyCompleted,
+    /// Worth retrying: lost connections, timeouts, deadlocks.
+    Unavailable(String),
+    Backend(String),
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::DuplicateParticipant => write!(f, "participant id already exists"),
+            StorageError::UnknownParticipant => write!(f, "unknown participant id"),
+            StorageError::WrongToken => write!(f, "wrong session token"),
+            StorageError::AlreadyCompleted => write!(f, "participant already submitted a result"),
+            StorageError::Unavailable(e) => write!(f, "storage temporarily unavailable: {}", e),
+            StorageError::Backend(e) => write!(f, "storage backend error: {}", e),
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError::Backend(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Backend(e.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionRecord {
+    pub participant_id: i32,
+    pub session_token: String,
+    pub adviser_mode: AdviserMode,
+    /// unix seconds
+    pub started_at: u64,
+    /// pilot sessions are kept apart from the real study
+    #[serde(default)]
+    pub pilot: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResultRecord {
+    pub participant_id: i32,
+    pub adviser_mode: AdviserMode,
+    pub steps_taken: u32,
+    pub safety_violated: u32,
+    pub human_burgers: u32,
+    pub robot_burgers: u32,
+    /// unix seconds
+    pub submitted_at: u64,
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(default)]
+    pub study_id: String,
+    #[serde(default)]
+    pub client_build: String,
+    /// unix milliseconds, as reported by the client
+    #[serde(default)]
+    pub client_started_at: u64,
+    /// unix milliseconds, as reported by the client
+    #[serde(default)]
+    pub client_submitted_at: u64,
+    /// plausibility flags raised during validation, empty for unremarkable results
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// chosen by the client, so that a retried submission can be recognized
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StepRecord {
+    pub participant_id: i32,
+    pub event: StepEvent,
+}
+
+impl ResultRecord {
+    pub fn new(
+        submission: &ResultSubmission,
+        flags: &[Flag],
+        idempotency_key: Option<&str>,
+    ) -> Self {
+        let result = &submission.result;
+        ResultRecord {
+            participant_id: result.participant_id,
+            adviser_mode: result.adviser_mode,
+            steps_taken: result.steps_taken,
+            safety_violated: result.safety_violated,
+            human_burgers: result.human_burgers,
+            robot_burgers: result.robot_burgers,
+            submitted_at: unix_now(),
+            schema_version: submission.schema_version,
+            study_id: submission.study_id.clone(),
+            client_build: submission.client_build.clone(),
+            client_started_at: submission.started_at,
+            client_submitted_at: submission.submitted_at,
+            flags: flags.iter().map(|f| f.to_string()).collect(),
+            idempotency_key: idempotency_key.map(str::to_owned),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertOutcome {
+    Inserted,
+    /// The same submission was stored before, nothing changed.
+    Repeated,
+}
+
+/// Runs `op` until it succeeds, fails with a non-transient error or runs out of retries,
+/// doubling the delay between attempts.
+pub async fn with_retry<T, F, Fut>(
+    retries: u32,
+    backoff: Duration,
+    mut op: F,
+) -> Result<T, StorageError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, StorageError>>,
+{
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(StorageError::Unavailable(e)) if attempt < retries => {
+                log::warn!("Storage unavailable ({}), retrying in {:?}.", e, delay);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Flags are stored as one comma separated column in the SQL backends.
+pub fn split_flags(flags: &str) -> Vec<String> {
+    flags
+        .split(',')
+        .filter(|f| !f.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Everything the server needs to persist. Session checks (unknown participant, wrong token,
+/// already completed) are done by the implementation so that they are atomic with the insert.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn create_schema(&self) -> Result<(), StorageError>;
+
+    /// Cheap check that the storage can be reached, without retries.
+    async fn ping(&self) -> Result<(), StorageError>;
+
+    /// Sessions per adviser mode, not counting pilot sessions.
+    async fn session_counts(&self) -> Result<HashMap<AdviserMode, u32>, StorageError>;
+
+    /// Fails with `DuplicateParticipant` if the participant id is already taken.
+    async fn insert_session(&self, session: &SessionRecord) -> Result<(), StorageError>;
+
+    /// Fails with `AlreadyCompleted` if the participant already submitted a result, unless that
+    /// result was sent with the same idempotency key.
+    async fn insert_result(
+        &self,
+        submission: &ResultSubmission,
+        flags: &[Flag],
+        idempotency_key: Option<&str>,
+    ) -> Result<InsertOutcome, StorageError>;
+
+    async fn insert_steps(&self, batch: &StepBatch) -> Result<(), StorageError>;
+
+    async fn sessions(&self) -> Result<Vec<SessionRecord>, StorageError>;
+
+    async fn results(&self) -> Result<Vec<ResultRecord>, StorageError>;
+
+    async fn steps(&self) -> Result<Vec<StepRecord>, StorageError>;
+}
+
+/// Picks the storage backend from `STORAGE_BACKEND` (`mysql`, `sqlite` or `jsonl`, default `mysql`).
+pub fn from_env() -> Result<Arc<dyn Storage>, StorageError> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "mysql".to_owned());
+    match backend.as_str() {
+        "mysql" => Ok(Arc::new(mysql::MysqlStorage::from_env()?)),
+        "sqlite" => {
+            let path = std::env::var("SQLITE_PATH").unwrap_or_else(|_| "study.sqlite".to_owned());
+            Ok(Arc::new(sqlite::SqliteStorage::open(&path)?))
+        }
+        "jsonl" => {
+            let dir = std::env::var("JSONL_DIR").unwrap_or_else(|_| "study_data".to_owned());
+            Ok(Arc::new(jsonl::JsonlStorage::open(&dir)?))
+        }
+        other => Err(StorageError::Backend(format!(
+            "unknown STORAGE_BACKEND {:?}",
+            other
+        ))),
+    }
+}
+
+/// Records shared by the tests of the storage backends.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use study_shared_types::{GameResults, InteractState, RESULT_SCHEMA_VERSION, STUDY_ID};
+
+    use super::*;
+
+    pub const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+    pub fn session(participant_id: i32) -> SessionRecord {
+        SessionRecord {
+            participant_id,
+            session_token: TOKEN.to_owned(),
+            adviser_mode: AdviserMode::LeastLimiting,
+            started_at: unix_now(),
+            pilot: false,
+        }
+    }
+
+    pub fn steps(participant_id: i32, steps: std::ops::Range<u32>) -> StepBatch {
+        StepBatch {
+            participant_id,
+            session_token: TOKEN.to_owned(),
+            events: steps
+                .map(|step| StepEvent {
+                    step,
+                    timestamp_ms: step as u64 * 400,
+                    human_move: "Down".to_owned(),
+                    robot_move: "Idle".to_owned(),
+                    human_pos: (1, 1),
+                    robot_pos: (3, 1),
+                    human_interact: InteractState::No,
+                    robot_interact: InteractState::No,
+                    observation: "00000".to_owned(),
+                    game_state: ("0".to_owned(), "0".to_owned(), "0".to_owned()),
+                    safety_guards: Vec::new(),
+                    fairness_guards: Vec::new(),
+                    violated: false,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn submission(participant_id: i32, session_token: &str) -> ResultSubmission {
+        ResultSubmission {
+            schema_version: RESULT_SCHEMA_VERSION,
+            study_id: STUDY_ID.to_owned(),
+            client_build: "test".to_owned(),
+            started_at: 0,
+            submitted_at: 0,
+            result: GameResults {
+                participant_id,
+                session_token: session_token.to_owned(),
+                adviser_mode: AdviserMode::LeastLimiting,
+                steps_taken: 120,
+                safety_violated: 0,
+                human_burgers: 2,
+                robot_burgers: 3,
+            },
+        }
+    }
+
+    /// The session checks of `insert_result` and how it answers repeated submissions, the same
+    /// for every backend.
+    pub async fn check_result_idempotency(storage: &dyn Storage) {
+        storage.create_schema().await.unwrap();
+        for participant_id in [100001, 100002] {
+            storage
+                .insert_session(&session(participant_id))
+                .await
+                .unwrap();
+        }
+
+        let insert = |participant_id, token: &'static str, key: Option<&'static str>| async move {
+            storage
+                .insert_result(&submission(participant_id, token), &[], key)
+                .await
+        };
+        assert!(matches!(
+            insert(999999, TOKEN, Some("a")).await,
+            Err(StorageError::UnknownParticipant)
+        ));
+        assert!(matches!(
+            insert(100001, "wrong", Some("a")).await,
+            Err(StorageError::WrongToken)
+        ));
+
+        assert!(matches!(
+            insert(100001, TOKEN, Some("a")).await,
+            Ok(InsertOutcome::Inserted)
+        ));
+        assert!(matches!(
+            insert(100001, TOKEN, Some("a")).await,
+            Ok(InsertOutcome::Repeated)
+        ));
+        assert!(matches!(
+            insert(100001, TOKEN, Some("b")).await,
+            Err(StorageError::AlreadyCompleted)
+        ));
+        assert!(matches!(
+            insert(100001, TOKEN, None).await,
+            Err(StorageError::AlreadyCompleted)
+        ));
+        // a wrong token is refused even for a repeated submission
+        assert!(matches!(
+            insert(100001, "wrong", Some("a")).await,
+            Err(StorageError::WrongToken)
+        ));
+
+        // without a key, nothing can be recognized as a retry
+        assert!(matches!(
+            insert(100002, TOKEN, None).await,
+            Ok(InsertOutcome::Inserted)
+        ));
+        assert!(matches!(
+            insert(100002, TOKEN, None).await,
+            Err(StorageError::AlreadyCompleted)
+        ));
+        assert!(matches!(
+            insert(100002, TOKEN, Some("a")).await,
+            Err(StorageError::AlreadyCompleted)
+        ));
+
+        assert_eq!(storage.results().await.unwrap().len(), 2);
+    }
+
+    /// A directory of its own below the system temp dir, emptied first.
+    pub fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("study_backend_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+}
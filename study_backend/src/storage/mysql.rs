@@ -0,0 +1,538 @@
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use async_trait::async_trait;
+use mysql_async::{prelude::*, OptsBuilder, PoolConstraints, PoolOpts, Row, TxOpts};
+use study_shared_types::{AdviserMode, InteractState, ResultSubmission, StepBatch, StepEvent};
+
+use crate::validation::Flag;
+
+use super::{
+    split_flags, with_retry, ResultRecord, SessionRecord, StepRecord, Storage, StorageError,
+};
+
+const MYSQL_DUPLICATE_ENTRY: u16 = 1062;
+/// Server errors that go away on their own: too many connections, lock wait timeout, deadlock.
+const MYSQL_TRANSIENT_ERRORS: [u16; 3] = [1040, 1205, 1213];
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+impl From<mysql_async::Error> for StorageError {
+    fn from(e: mysql_async::Error) -> Self {
+        match &e {
+            mysql_async::Error::Io(_) => StorageError::Unavailable(e.to_string()),
+            mysql_async::Error::Server(s) if MYSQL_TRANSIENT_ERRORS.contains(&s.code) => {
+                StorageError::Unavailable(e.to_string())
+            }
+            _ => StorageError::Backend(e.to_string()),
+        }
+    }
+}
+
+/// Holds one connection pool for the lifetime of the server.
+pub struct MysqlStorage {
+    pool: mysql_async::Pool,
+    connect_timeout: Duration,
+    retries: u32,
+}
+
+impl MysqlStorage {
+    pub fn from_env() -> Result<Self, StorageError> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| StorageError::Backend(format!("{} must be set.", name)))
+        };
+        let var_or = |name: &str, default: u64| -> Result<u64, StorageError> {
+            match std::env::var(name) {
+                Ok(v) => v
+                    .parse::<u64>()
+                    .map_err(|_| StorageError::Backend(format!("{} is not a number.", name))),
+                Err(_) => Ok(default),
+            }
+        };
+        let database_port = var("DATABASE_PORT")?
+            .parse::<u16>()
+            .map_err(|_| StorageError::Backend("DATABASE_PORT is not a u16.".to_owned()))?;
+
+        let pool_min = var_or("DATABASE_POOL_MIN", 1)? as usize;
+        let pool_max = var_or("DATABASE_POOL_MAX", 20)? as usize;
+        let constraints = PoolConstraints::new(pool_min, pool_max).ok_or_else(|| {
+            StorageError::Backend("DATABASE_POOL_MIN must not exceed DATABASE_POOL_MAX.".to_owned())
+        })?;
+
+        let opts = OptsBuilder::default()
+            .ip_or_hostname(var("DATABASE_URL")?)
+            .tcp_port(database_port)
+            .user(Some(var("DATABASE_USER")?))
+            .pass(Some(var("DATABASE_PASS")?))
+            .db_name(Some(var("DATABASE_NAME")?))
+            .pool_opts(PoolOpts::default().with_constraints(constraints));
+
+        Ok(MysqlStorage {
+            pool: mysql_async::Pool::new(opts),
+            connect_timeout: Duration::from_secs(var_or("DATABASE_CONNECT_TIMEOUT", 5)?),
+            retries: var_or("DATABASE_RETRIES", 3)? as u32,
+        })
+    }
+
+    async fn conn(&self) -> Result<mysql_async::Conn, StorageError> {
+        match tokio::time::timeout(self.connect_timeout, self.pool.get_conn()).await {
+            Ok(conn) => Ok(conn?),
+            Err(_) => Err(StorageError::Unavailable(
+                "timed out waiting for a connection".to_owned(),
+            )),
+        }
+    }
+
+    async fn retry<T, F, Fut>(&self, op: F) -> Result<T, StorageError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, StorageError>>,
+    {
+        with_retry(self.retries, RETRY_BACKOFF, op).await
+    }
+}
+
+/// Columns added to `study_data` after its first version, with their definitions.
+const RESULT_COLUMNS: [(&str, &str); 8] = [
+    (
+        "submitted_at",
+        "timestamp not null default current_timestamp",
+    ),
+    ("schema_version", "int not null default 1"),
+    ("study_id", "varchar(64) not null default ''"),
+    ("client_build", "varchar(64) not null default ''"),
+    ("client_started_at", "bigint not null default 0"),
+    ("client_submitted_at", "bigint not null default 0"),
+    ("flags", "varchar(255) not null default ''"),
+    ("idempotency_key", "varchar(64) null"),
+];
+
+/// Columns added to `study_sessions` after its first version. Sessions from before the
+/// participant ids have none, so the id may be null there.
+const SESSION_COLUMNS: [(&str, &str); 4] = [
+    ("participant_id", "int null unique"),
+    ("session_token", "char(32) not null default ''"),
+    ("completed_at", "timestamp null"),
+    ("pilot", "bool not null default false"),
+];
+
+async fn ensure_column(
+    conn: &mut mysql_async::Conn,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), StorageError> {
+    let exists: Option<u32> = r"SELECT COUNT(*) FROM information_schema.columns
+      WHERE table_schema = DATABASE() AND table_name = :table AND column_name = :column"
+        .with(params! {
+            "table" => table,
+            "column" => column,
+        })
+        .first(&mut *conn)
+        .await?;
+    if exists == Some(0) {
+        format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition)
+            .ignore(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Resent step batches must not store a step twice. Tables created before the key existed may
+/// already hold duplicates, of which the first copy is kept.
+async fn ensure_step_key(conn: &mut mysql_async::Conn) -> Result<(), StorageError> {
+    let exists: Option<u32> = r"SELECT COUNT(*) FROM information_schema.statistics
+      WHERE table_schema = DATABASE() AND table_name = 'study_steps' AND index_name = 'participant_step'"
+        .first(&mut *conn)
+        .await?;
+    if exists == Some(0) {
+        r"DELETE later FROM study_steps later JOIN study_steps earlier
+          ON later.participant_id = earlier.participant_id AND later.step = earlier.step
+            AND later.step_id > earlier.step_id"
+            .ignore(&mut *conn)
+            .await?;
+        r"ALTER TABLE study_steps ADD UNIQUE KEY participant_step (participant_id, step)"
+            .ignore(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+fn parse_interact(s: &str) -> InteractState {
+    match s {
+        "In" => InteractState::In,
+        "Out" => InteractState::Out,
+        "Stay" => InteractState::Stay,
+        _ => InteractState::No,
+    }
+}
+
+fn result_from_row(mut row: Row) -> Option<ResultRecord> {
+    let adviser_mode: String = row.take("adviser_mode")?;
+    let flags: String = row.take("flags")?;
+
+    Some(ResultRecord {
+        participant_id: row.take("participant_id")?,
+        adviser_mode: AdviserMode::from_name(&adviser_mode)?,
+        steps_taken: row.take("steps_taken")?,
+        safety_violated: row.take("safety_violated")?,
+        human_burgers: row.take("human_burgers")?,
+        robot_burgers: row.take("robot_burgers")?,
+        submitted_at: row.take("submitted_at")?,
+        schema_version: row.take("schema_version")?,
+        study_id: row.take("study_id")?,
+        client_build: row.take("client_build")?,
+        client_started_at: row.take("client_started_at")?,
+        client_submitted_at: row.take("client_submitted_at")?,
+        flags: split_flags(&flags),
+        idempotency_key: row.take("idempotency_key")?,
+    })
+}
+
+fn step_from_row(mut row: Row) -> Option<StepRecord> {
+    let human_interact: String = row.take("human_interact")?;
+    let robot_interact: String = row.take("robot_interact")?;
+    let game_state: String = row.take("game_state")?;
+    let safety_guards: String = row.take("safety_guards")?;
+    let fairness_guards: String = row.take("fairness_guards")?;
+
+    Some(StepRecord {
+        participant_id: row.take("participant_id")?,
+        event: StepEvent {
+            step: row.take("step")?,
+            timestamp_ms: row.take("timestamp_ms")?,
+            human_move: row.take("human_move")?,
+            robot_move: row.take("robot_move")?,
+            human_pos: (row.take("human_x")?, row.take("human_y")?),
+            robot_pos: (row.take("robot_x")?, row.take("robot_y")?),
+            human_interact: parse_interact(&human_interact),
+            robot_interact: parse_interact(&robot_interact),
+            observation: row.take("observation")?,
+            game_state: serde_json::from_str(&game_state).ok()?,
+            safety_guards: serde_json::from_str(&safety_guards).ok()?,
+            fairness_guards: serde_json::from_str(&fairness_guards).ok()?,
+            violated: row.take("violated")?,
+        },
+    })
+}
+
+#[async_trait]
+impl Storage for MysqlStorage {
+    async fn create_schema(&self) -> Result<(), StorageError> {
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            r"CREATE TABLE if not exists study_data (
+                participant_id int primary key,
+                adviser_mode ENUM('LeastLimiting', 'NextMove', 'None'),
+                steps_taken int not null,
+                safety_violated int not null,
+                human_burgers int not null,
+                robot_burgers int not null,
+                submitted_at timestamp not null default current_timestamp,
+                schema_version int not null default 1,
+                study_id varchar(64) not null default '',
+                client_build varchar(64) not null default '',
+                client_started_at bigint not null default 0,
+                client_submitted_at bigint not null default 0,
+                flags varchar(255) not null default '',
+                idempotency_key varchar(64) null
+            )"
+            .ignore(&mut conn)
+            .await?;
+
+            // tables created by older versions lack the later columns
+            for (column, definition) in RESULT_COLUMNS {
+                ensure_column(&mut conn, "study_data", column, definition).await?;
+            }
+
+            r"CREATE TABLE if not exists study_sessions (
+                session_id int auto_increment primary key,
+                participant_id int not null unique,
+                session_token char(32) not null,
+                adviser_mode ENUM('LeastLimiting', 'NextMove', 'None') not null,
+                started_at timestamp not null default current_timestamp,
+                completed_at timestamp null,
+                pilot bool not null default false
+            )"
+            .ignore(&mut conn)
+            .await?;
+            for (column, definition) in SESSION_COLUMNS {
+                ensure_column(&mut conn, "study_sessions", column, definition).await?;
+            }
+
+            r"CREATE TABLE if not exists study_steps (
+                step_id bigint auto_increment primary key,
+                participant_id int not null,
+                step int not null,
+                timestamp_ms bigint not null,
+                human_move varchar(16) not null,
+                robot_move varchar(16) not null,
+                human_x int not null,
+                human_y int not null,
+                robot_x int not null,
+                robot_y int not null,
+                human_interact ENUM('No', 'In', 'Out', 'Stay') not null,
+                robot_interact ENUM('No', 'In', 'Out', 'Stay') not null,
+                observation varchar(64) not null,
+                game_state text not null,
+                safety_guards text not null,
+                fairness_guards text not null,
+                violated bool not null,
+                unique key participant_step (participant_id, step)
+            )"
+            .ignore(&mut conn)
+            .await?;
+            ensure_step_key(&mut conn).await?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        let mut conn = self.conn().await?;
+        r"SELECT 1".ignore(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn session_counts(&self) -> Result<HashMap<AdviserMode, u32>, StorageError> {
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            let rows: Vec<(String, u32)> =
+                r"SELECT adviser_mode, COUNT(*) FROM study_sessions WHERE NOT pilot GROUP BY adviser_mode"
+                    .fetch(&mut conn)
+                    .await?;
+
+            Ok(rows
+                .into_iter()
+                .filter_map(|(mode, count)| AdviserMode::from_name(&mode).map(|m| (m, count)))
+                .collect())
+        })
+        .await
+    }
+
+    async fn insert_session(&self, session: &SessionRecord) -> Result<(), StorageError> {
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            let query = r"INSERT INTO study_sessions (participant_id, session_token, adviser_mode, started_at, pilot)
+              VALUES (:participant_id, :session_token, :adviser_mode, FROM_UNIXTIME(:started_at), :pilot)"
+                .with(params! {
+                    "participant_id" => session.participant_id,
+                    "session_token" => &session.session_token,
+                    "adviser_mode" => session.adviser_mode.to_string(),
+                    "started_at" => session.started_at,
+                    "pilot" => session.pilot,
+                });
+            match query.ignore(&mut conn).await {
+                Ok(_) => (),
+                Err(mysql_async::Error::Server(e)) if e.code == MYSQL_DUPLICATE_ENTRY => {
+                    return Err(StorageError::DuplicateParticipant);
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn insert_result(
+        &self,
+        submission: &ResultSubmission,
+        flags: &[Flag],
+        idempotency_key: Option<&str>,
+    ) -> Result<InsertOutcome, StorageError> {
+        let result = &submission.result;
+        let record = &ResultRecord::new(submission, flags, idempotency_key);
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            let mut tx = conn.start_transaction(TxOpts::default()).await?;
+
+            // results are only accepted for sessions we handed out, and only once
+            let session: Option<(String, bool)> = r"SELECT session_token, completed_at IS NOT NULL
+              FROM study_sessions WHERE participant_id = :participant_id FOR UPDATE"
+                .with(params! {
+                    "participant_id" => result.participant_id,
+                })
+                .first(&mut tx)
+                .await?;
+            match session {
+                None => return Err(StorageError::UnknownParticipant),
+                Some((token, _)) if token != result.session_token => {
+                    return Err(StorageError::WrongToken)
+                }
+                Some((_, true)) => {
+                    let stored_key: Option<Option<String>> =
+                        r"SELECT idempotency_key FROM study_data WHERE participant_id = :participant_id"
+                            .with(params! {
+                                "participant_id" => result.participant_id,
+                            })
+                            .first(&mut tx)
+                            .await?;
+                    return match (stored_key.flatten(), &record.idempotency_key) {
+                        (Some(stored), Some(given)) if stored == *given => {
+                            Ok(InsertOutcome::Repeated)
+                        }
+                        _ => Err(StorageError::AlreadyCompleted),
+                    };
+                }
+                Some((_, false)) => (),
+            }
+
+            r"INSERT INTO study_data (participant_id, adviser_mode, steps_taken, safety_violated, human_burgers, robot_burgers, submitted_at,
+                schema_version, study_id, client_build, client_started_at, client_submitted_at, flags,
+                idempotency_key)
+              VALUES (:participant_id, :adviser_mode, :steps_taken, :safety_violated, :human_burgers, :robot_burgers, FROM_UNIXTIME(:submitted_at),
+                :schema_version, :study_id, :client_build, :client_started_at, :client_submitted_at, :flags,
+                :idempotency_key)"
+                .with(params! {
+                    "participant_id" => record.participant_id,
+                    "adviser_mode" => record.adviser_mode.to_string(),
+                    "steps_taken" => record.steps_taken,
+                    "safety_violated" => record.safety_violated,
+                    "human_burgers" => record.human_burgers,
+                    "robot_burgers" => record.robot_burgers,
+                    "submitted_at" => record.submitted_at,
+                    "schema_version" => record.schema_version,
+                    "study_id" => &record.study_id,
+                    "client_build" => &record.client_build,
+                    "client_started_at" => record.client_started_at,
+                    "client_submitted_at" => record.client_submitted_at,
+                    "flags" => record.flags.join(","),
+                    "idempotency_key" => &record.idempotency_key,
+                })
+                .ignore(&mut tx)
+                .await?;
+
+            r"UPDATE study_sessions SET completed_at = FROM_UNIXTIME(:submitted_at)
+              WHERE participant_id = :participant_id"
+                .with(params! {
+                    "participant_id" => record.participant_id,
+                    "submitted_at" => record.submitted_at,
+                })
+                .ignore(&mut tx)
+                .await?;
+
+            tx.commit().await?;
+
+            Ok(InsertOutcome::Inserted)
+        })
+        .await
+    }
+
+    async fn insert_steps(&self, batch: &StepBatch) -> Result<(), StorageError> {
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            // all or nothing, so that a retry after a lost connection starts from a clean slate
+            let mut tx = conn.start_transaction(TxOpts::default()).await?;
+
+            // the last batch may arrive after the result, so completed sessions are fine here
+            let token: Option<String> =
+                r"SELECT session_token FROM study_sessions WHERE participant_id = :participant_id"
+                    .with(params! {
+                        "participant_id" => batch.participant_id,
+                    })
+                    .first(&mut tx)
+                    .await?;
+            match token {
+                None => return Err(StorageError::UnknownParticipant),
+                Some(token) if token != batch.session_token => return Err(StorageError::WrongToken),
+                Some(_) => (),
+            }
+
+            // steps stored by an earlier copy of the batch are skipped
+            let participant_id = batch.participant_id;
+            r"INSERT IGNORE INTO study_steps (participant_id, step, timestamp_ms, human_move, robot_move,
+                human_x, human_y, robot_x, robot_y, human_interact, robot_interact, observation, game_state,
+                safety_guards, fairness_guards, violated)
+              VALUES (:participant_id, :step, :timestamp_ms, :human_move, :robot_move,
+                :human_x, :human_y, :robot_x, :robot_y, :human_interact, :robot_interact, :observation, :game_state,
+                :safety_guards, :fairness_guards, :violated)"
+                .with(batch.events.iter().map(|event| {
+                    params! {
+                        "participant_id" => participant_id,
+                        "step" => event.step,
+                        "timestamp_ms" => event.timestamp_ms,
+                        "human_move" => &event.human_move,
+                        "robot_move" => &event.robot_move,
+                        "human_x" => event.human_pos.0,
+                        "human_y" => event.human_pos.1,
+                        "robot_x" => event.robot_pos.0,
+                        "robot_y" => event.robot_pos.1,
+                        "human_interact" => event.human_interact.to_string(),
+                        "robot_interact" => event.robot_interact.to_string(),
+                        "observation" => &event.observation,
+                        "game_state" => serde_json::to_string(&event.game_state).unwrap_or_default(),
+                        "safety_guards" => serde_json::to_string(&event.safety_guards).unwrap_or_default(),
+                        "fairness_guards" => serde_json::to_string(&event.fairness_guards).unwrap_or_default(),
+                        "violated" => event.violated,
+                    }
+                }))
+                .batch(&mut tx)
+                .await?;
+
+            tx.commit().await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn sessions(&self) -> Result<Vec<SessionRecord>, StorageError> {
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            let rows: Vec<(i32, String, String, u64, bool)> =
+                r"SELECT participant_id, session_token,
+                adviser_mode, UNIX_TIMESTAMP(started_at), pilot
+              FROM study_sessions WHERE participant_id IS NOT NULL ORDER BY started_at"
+                    .fetch(&mut conn)
+                    .await?;
+
+            Ok(rows
+                .into_iter()
+                .filter_map(|(participant_id, session_token, mode, started_at, pilot)| {
+                    Some(SessionRecord {
+                        participant_id,
+                        session_token,
+                        adviser_mode: AdviserMode::from_name(&mode)?,
+                        started_at,
+                        pilot,
+                    })
+                })
+                .collect())
+        })
+        .await
+    }
+
+    async fn results(&self) -> Result<Vec<ResultRecord>, StorageError> {
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            let rows: Vec<Row> = r"SELECT participant_id, adviser_mode, steps_taken, safety_violated, human_burgers,
+                robot_burgers, UNIX_TIMESTAMP(submitted_at) AS submitted_at, schema_version, study_id,
+                client_build, client_started_at, client_submitted_at, flags, idempotency_key
+              FROM study_data ORDER BY submitted_at"
+                .fetch(&mut conn)
+                .await?;
+
+            Ok(rows.into_iter().filter_map(result_from_row).collect())
+        })
+        .await
+    }
+
+    async fn steps(&self) -> Result<Vec<StepRecord>, StorageError> {
+        self.retry(|| async move {
+            let mut conn = self.conn().await?;
+
+            let rows: Vec<Row> = r"SELECT * FROM study_steps ORDER BY participant_id, step"
+                .fetch(&mut conn)
+                .await?;
+
+            Ok(rows.into_iter().filter_map(step_from_row).collect())
+        })
+        .await
+    }
+}
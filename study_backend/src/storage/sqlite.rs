@@ -0,0 +1,500 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension};
+use study_shared_types::{AdviserMode, ResultSubmission, StepBatch, StepEvent};
+
+use crate::validation::Flag;
+
+use super::{
+    split_flags, InsertOutcome, ResultRecord, SessionRecord, StepRecord, Storage, StorageError,
+};
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        StorageError::Backend(e.to_string())
+    }
+}
+
+/// Columns added after the first version of the tables, with their definitions.
+const ADDED_COLUMNS: [(&str, &str, &str); 8] = [
+    ("study_data", "schema_version", "integer not null default 1"),
+    ("study_data", "study_id", "text not null default ''"),
+    ("study_data", "client_build", "text not null default ''"),
+    (
+        "study_data",
+        "client_started_at",
+        "integer not null default 0",
+    ),
+    (
+        "study_data",
+        "client_submitted_at",
+        "integer not null default 0",
+    ),
+    ("study_data", "flags", "text not null default ''"),
+    ("study_data", "idempotency_key", "text"),
+    ("study_sessions", "pilot", "integer not null default 0"),
+];
+
+fn ensure_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), StorageError> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))?;
+    }
+    Ok(())
+}
+
+/// A single SQLite file, meant for local development and pilot runs.
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, StorageError> {
+        let conn = Connection::open(path)?;
+        Ok(SqliteStorage {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// rusqlite is blocking, so every access runs on the blocking thread pool.
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, StorageError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| StorageError::Backend("sqlite connection poisoned".to_owned()))?;
+            f(&mut conn)
+        })
+        .await
+        .map_err(|e| StorageError::Backend(e.to_string()))?
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn create_schema(&self) -> Result<(), StorageError> {
+        self.with_conn(|conn| {
+            conn.execute_batch(
+                r"CREATE TABLE if not exists study_data (
+                    participant_id integer primary key,
+                    adviser_mode text not null,
+                    steps_taken integer not null,
+                    safety_violated integer not null,
+                    human_burgers integer not null,
+                    robot_burgers integer not null,
+                    submitted_at integer not null,
+                    schema_version integer not null,
+                    study_id text not null,
+                    client_build text not null,
+                    client_started_at integer not null,
+                    client_submitted_at integer not null,
+                    flags text not null default '',
+                    idempotency_key text
+                );
+                CREATE TABLE if not exists study_sessions (
+                    session_id integer primary key autoincrement,
+                    participant_id integer not null unique,
+                    session_token text not null,
+                    adviser_mode text not null,
+                    started_at integer not null,
+                    completed_at integer,
+                    pilot integer not null default 0
+                );
+                CREATE TABLE if not exists study_steps (
+                    step_id integer primary key autoincrement,
+                    participant_id integer not null,
+                    step integer not null,
+                    event text not null
+                );
+                CREATE INDEX if not exists study_steps_participant ON study_steps (participant_id);",
+            )?;
+            // files written by older versions lack the later columns
+            for (table, column, definition) in ADDED_COLUMNS {
+                ensure_column(conn, table, column, definition)?;
+            }
+            // resent step batches must not store a step twice, older files keep the first copy
+            let keyed: bool = conn.query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master
+                 WHERE type = 'index' AND name = 'study_steps_participant_step'",
+                [],
+                |row| row.get(0),
+            )?;
+            if !keyed {
+                conn.execute_batch(
+                    r"DELETE FROM study_steps WHERE step_id NOT IN
+                        (SELECT MIN(step_id) FROM study_steps GROUP BY participant_id, step);
+                    CREATE UNIQUE INDEX study_steps_participant_step
+                        ON study_steps (participant_id, step);",
+                )?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.with_conn(|conn| {
+            conn.query_row("SELECT 1", [], |_| Ok(()))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn session_counts(&self) -> Result<HashMap<AdviserMode, u32>, StorageError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT adviser_mode, COUNT(*) FROM study_sessions WHERE NOT pilot GROUP BY adviser_mode",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?))
+            })?;
+
+            let mut counts = HashMap::new();
+            for row in rows {
+                let (mode, count) = row?;
+                if let Some(mode) = AdviserMode::from_name(&mode) {
+                    counts.insert(mode, count);
+                }
+            }
+            Ok(counts)
+        })
+        .await
+    }
+
+    async fn insert_session(&self, session: &SessionRecord) -> Result<(), StorageError> {
+        let session = session.clone();
+        self.with_conn(move |conn| {
+            let inserted = conn.execute(
+                "INSERT INTO study_sessions (participant_id, session_token, adviser_mode, started_at, pilot)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    session.participant_id,
+                    session.session_token,
+                    session.adviser_mode.to_string(),
+                    session.started_at,
+                    session.pilot
+                ],
+            );
+            match inserted {
+                Ok(_) => Ok(()),
+                Err(rusqlite::Error::SqliteFailure(e, _))
+                    if e.code == ErrorCode::ConstraintViolation =>
+                {
+                    Err(StorageError::DuplicateParticipant)
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await
+    }
+
+    async fn insert_result(
+        &self,
+        submission: &ResultSubmission,
+        flags: &[Flag],
+        idempotency_key: Option<&str>,
+    ) -> Result<InsertOutcome, StorageError> {
+        let record = ResultRecord::new(submission, flags, idempotency_key);
+        let session_token = submission.result.session_token.clone();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+
+            // results are only accepted for sessions we handed out, and only once
+            let session: Option<(String, bool)> = tx
+                .query_row(
+                    "SELECT session_token, completed_at IS NOT NULL
+                     FROM study_sessions WHERE participant_id = ?1",
+                    params![record.participant_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            match session {
+                None => return Err(StorageError::UnknownParticipant),
+                Some((token, _)) if token != session_token => return Err(StorageError::WrongToken),
+                Some((_, true)) => {
+                    let stored_key: Option<String> = tx.query_row(
+                        "SELECT idempotency_key FROM study_data WHERE participant_id = ?1",
+                        params![record.participant_id],
+                        |row| row.get(0),
+                    )?;
+                    return match (stored_key, &record.idempotency_key) {
+                        (Some(stored), Some(given)) if stored == *given => {
+                            Ok(InsertOutcome::Repeated)
+                        }
+                        _ => Err(StorageError::AlreadyCompleted),
+                    };
+                }
+                Some((_, false)) => (),
+            }
+
+            tx.execute(
+                "INSERT INTO study_data (participant_id, adviser_mode, steps_taken, safety_violated, human_burgers, robot_burgers, submitted_at,
+                    schema_version, study_id, client_build, client_started_at, client_submitted_at, flags, idempotency_key)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    record.participant_id,
+                    record.adviser_mode.to_string(),
+                    record.steps_taken,
+                    record.safety_violated,
+                    record.human_burgers,
+                    record.robot_burgers,
+                    record.submitted_at,
+                    record.schema_version,
+                    record.study_id,
+                    record.client_build,
+                    record.client_started_at,
+                    record.client_submitted_at,
+                    record.flags.join(","),
+                    record.idempotency_key
+                ],
+            )?;
+            tx.execute(
+                "UPDATE study_sessions SET completed_at = ?1 WHERE participant_id = ?2",
+                params![record.submitted_at, record.participant_id],
+            )?;
+
+            tx.commit()?;
+            Ok(InsertOutcome::Inserted)
+        })
+        .await
+    }
+
+    async fn insert_steps(&self, batch: &StepBatch) -> Result<(), StorageError> {
+        let participant_id = batch.participant_id;
+        let session_token = batch.session_token.clone();
+        let events = batch
+            .events
+            .iter()
+            .map(|event| Ok((event.step, serde_json::to_string(event)?)))
+            .collect::<Result<Vec<(u32, String)>, StorageError>>()?;
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+
+            // the last batch may arrive after the result, so completed sessions are fine here
+            let token: Option<String> = tx
+                .query_row(
+                    "SELECT session_token FROM study_sessions WHERE participant_id = ?1",
+                    params![participant_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match token {
+                None => return Err(StorageError::UnknownParticipant),
+                Some(token) if token != session_token => return Err(StorageError::WrongToken),
+                Some(_) => (),
+            }
+
+            // steps stored by an earlier copy of the batch are skipped
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO study_steps (participant_id, step, event) VALUES (?1, ?2, ?3)
+                     ON CONFLICT DO NOTHING",
+                )?;
+                for (step, event) in &events {
+                    stmt.execute(params![participant_id, step, event])?;
+                }
+            }
+
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn sessions(&self) -> Result<Vec<SessionRecord>, StorageError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT participant_id, session_token, adviser_mode, started_at, pilot
+                 FROM study_sessions ORDER BY started_at",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let mode: String = row.get(2)?;
+                let adviser_mode = match AdviserMode::from_name(&mode) {
+                    Some(m) => m,
+                    None => return Ok(None),
+                };
+                Ok(Some(SessionRecord {
+                    participant_id: row.get(0)?,
+                    session_token: row.get(1)?,
+                    adviser_mode,
+                    started_at: row.get(3)?,
+                    pilot: row.get(4)?,
+                }))
+            })?;
+
+            let mut records = Vec::new();
+            for row in rows {
+                if let Some(record) = row? {
+                    records.push(record);
+                }
+            }
+            Ok(records)
+        })
+        .await
+    }
+
+    async fn results(&self) -> Result<Vec<ResultRecord>, StorageError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT participant_id, adviser_mode, steps_taken, safety_violated, human_burgers, robot_burgers, submitted_at,
+                    schema_version, study_id, client_build, client_started_at, client_submitted_at, flags,
+                    idempotency_key
+                 FROM study_data ORDER BY submitted_at",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let mode: String = row.get(1)?;
+                let flags: String = row.get(12)?;
+                let adviser_mode = match AdviserMode::from_name(&mode) {
+                    Some(m) => m,
+                    None => return Ok(None),
+                };
+                Ok(Some(ResultRecord {
+                    participant_id: row.get(0)?,
+                    adviser_mode,
+                    steps_taken: row.get(2)?,
+                    safety_violated: row.get(3)?,
+                    human_burgers: row.get(4)?,
+                    robot_burgers: row.get(5)?,
+                    submitted_at: row.get(6)?,
+                    schema_version: row.get(7)?,
+                    study_id: row.get(8)?,
+                    client_build: row.get(9)?,
+                    client_started_at: row.get(10)?,
+                    client_submitted_at: row.get(11)?,
+                    flags: split_flags(&flags),
+                    idempotency_key: row.get(13)?,
+                }))
+            })?;
+
+            let mut records = Vec::new();
+            for row in rows {
+                if let Some(record) = row? {
+                    records.push(record);
+                }
+            }
+            Ok(records)
+        })
+        .await
+    }
+
+    async fn steps(&self) -> Result<Vec<StepRecord>, StorageError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT participant_id, event FROM study_steps ORDER BY participant_id, step",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+            })?;
+
+            let mut records = Vec::new();
+            for row in rows {
+                let (participant_id, event) = row?;
+                let event: StepEvent = serde_json::from_str(&event)?;
+                records.push(StepRecord {
+                    participant_id,
+                    event,
+                });
+            }
+            Ok(records)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::fixtures;
+
+    async fn storage() -> SqliteStorage {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        storage.create_schema().await.unwrap();
+        storage
+    }
+
+    #[tokio::test]
+    async fn results_are_stored_once() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        fixtures::check_result_idempotency(&storage).await;
+    }
+
+    #[tokio::test]
+    async fn resent_steps_are_stored_once() {
+        let storage = storage().await;
+        storage
+            .insert_session(&fixtures::session(100001))
+            .await
+            .unwrap();
+
+        storage
+            .insert_steps(&fixtures::steps(100001, 0..10))
+            .await
+            .unwrap();
+        storage
+            .insert_steps(&fixtures::steps(100001, 0..10))
+            .await
+            .unwrap();
+        storage
+            .insert_steps(&fixtures::steps(100001, 5..15))
+            .await
+            .unwrap();
+
+        let steps: Vec<u32> = storage
+            .steps()
+            .await
+            .unwrap()
+            .iter()
+            .map(|s| s.event.step)
+            .collect();
+        assert_eq!(steps, (0..15).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn old_files_get_the_later_columns() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        storage
+            .with_conn(|conn| {
+                conn.execute_batch(
+                    r"CREATE TABLE study_sessions (
+                        session_id integer primary key autoincrement,
+                        participant_id integer not null unique,
+                        session_token text not null,
+                        adviser_mode text not null,
+                        started_at integer not null,
+                        completed_at integer
+                    );",
+                )?;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        storage.create_schema().await.unwrap();
+
+        let session = SessionRecord {
+            pilot: true,
+            ..fixtures::session(100001)
+        };
+        storage.insert_session(&session).await.unwrap();
+        assert!(storage.sessions().await.unwrap()[0].pilot);
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+use study_shared_types::{ResultSubmission, GAME_DURATION, MAX_STEPS, MIN_STEPS_PER_BURGER};
+
+/// Results this close to the step limit are accepted, but flagged.
+const STEPS_FLAG_RATIO: f64 = 0.9;
+
+/// Why a result was rejected. Serialized as the `reason` of a 422 response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Violation {
+    NegativeParticipantId,
+    InvalidAdviserMode,
+    TooManySteps,
+    TooManySafetyViolations,
+}
+
+/// Something odd about an accepted result, stored alongside it for manual review.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    NoSteps,
+    NearStepLimit,
+    BurgersAtLimit,
+    BurgersAboveLimit,
+    ShortSession,
+}
+
+impl Flag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Flag::NoSteps => "no_steps",
+            Flag::NearStepLimit => "near_step_limit",
+            Flag::BurgersAtLimit => "burgers_at_limit",
+            Flag::BurgersAboveLimit => "burgers_above_limit",
+            Flag::ShortSession => "short_session",
+        }
+    }
+}
+
+impl Display for Flag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Body of a 422 response.
+#[derive(Debug, Serialize)]
+pub struct Rejection {
+    pub reason: Violation,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new(reason: Violation, message: String) -> Self {
+        Rejection { reason, message }
+    }
+}
+
+/// Deliveries possible within `steps` in the shipped kitchen. The first burger is one step
+/// faster, because the participant starts on the delivery counter without having to leave it
+/// first. Smaller layouts allow more, so this only raises flags.
+fn max_burgers(steps: u32) -> u32 {
+    (steps + 1) / MIN_STEPS_PER_BURGER
+}
+
+/// Checks a submission against what the game allows. Impossible results are rejected,
+/// implausible but possible ones come back as flags.
+pub fn validate(submission: &ResultSubmission) -> Result<Vec<Flag>, Rejection> {
+    let result = &submission.result;
+
+    if result.participant_id < 0 {
+        return Err(Rejection::new(
+            Violation::NegativeParticipantId,
+            format!("participant id {} is negative", result.participant_id),
+        ));
+    }
+    if result.steps_taken > MAX_STEPS {
+        return Err(Rejection::new(
+            Violation::TooManySteps,
+            format!(
+                "{} steps taken, at most {} fit into one game",
+                result.steps_taken, MAX_STEPS
+            ),
+        ));
+    }
+    if result.safety_violated > result.steps_taken {
+        return Err(Rejection::new(
+            Violation::TooManySafetyViolations,
+            format!(
+                "{} safety violations in {} steps",
+                result.safety_violated, result.steps_taken
+            ),
+        ));
+    }
+
+    let mut flags = Vec::new();
+    if result.steps_taken == 0 {
+        flags.push(Flag::NoSteps);
+    }
+    if result.steps_taken as f64 > MAX_STEPS as f64 * STEPS_FLAG_RATIO {
+        flags.push(Flag::NearStepLimit);
+    }
+    let max_burgers = max_burgers(result.steps_taken);
+    let most_burgers = result.human_burgers.max(result.robot_burgers);
+    if most_burgers > max_burgers {
+        flags.push(Flag::BurgersAboveLimit);
+    } else if max_burgers > 0 && most_burgers == max_burgers {
+        flags.push(Flag::BurgersAtLimit);
+    }
+    // v1 submissions carry no client timestamps
+    if submission.started_at > 0
+        && submission
+            .submitted_at
+            .saturating_sub(submission.started_at)
+            < GAME_DURATION.as_millis() as u64
+    {
+        flags.push(Flag::ShortSession);
+    }
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use study_shared_types::{AdviserMode, GameResults, RESULT_SCHEMA_VERSION, STUDY_ID};
+
+    use super::*;
+
+    /// An unremarkable result: a full game with a few burgers each.
+    fn submission() -> ResultSubmission {
+        ResultSubmission {
+            schema_version: RESULT_SCHEMA_VERSION,
+            study_id: STUDY_ID.to_owned(),
+            client_build: "test".to_owned(),
+            started_at: 1_000_000,
+            submitted_at: 1_000_000 + GAME_DURATION.as_millis() as u64 + 5_000,
+            result: GameResults {
+                participant_id: 123456,
+                session_token: String::new(),
+                adviser_mode: AdviserMode::NextMove,
+                steps_taken: 200,
+                safety_violated: 1,
+                human_burgers: 3,
+                robot_burgers: 4,
+            },
+        }
+    }
+
+    fn rejection(submission: &ResultSubmission) -> Violation {
+        validate(submission).unwrap_err().reason
+    }
+
+    #[test]
+    fn plausible_results_pass_unflagged() {
+        assert_eq!(validate(&submission()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn impossible_results_are_rejected() {
+        let mut s = submission();
+        s.result.participant_id = -1;
+        assert_eq!(rejection(&s), Violation::NegativeParticipantId);
+
+        let mut s = submission();
+        s.result.steps_taken = MAX_STEPS + 1;
+        assert_eq!(rejection(&s), Violation::TooManySteps);
+        s.result.steps_taken = MAX_STEPS;
+        assert!(validate(&s).is_ok());
+
+        let mut s = submission();
+        s.result.safety_violated = s.result.steps_taken + 1;
+        assert_eq!(rejection(&s), Violation::TooManySafetyViolations);
+    }
+
+    #[test]
+    fn step_flags() {
+        let mut s = submission();
+        s.result.steps_taken = 0;
+        s.result.safety_violated = 0;
+        s.result.human_burgers = 0;
+        s.result.robot_burgers = 0;
+        assert_eq!(validate(&s).unwrap(), vec![Flag::NoSteps]);
+
+        let threshold = (MAX_STEPS as f64 * STEPS_FLAG_RATIO) as u32;
+        let mut s = submission();
+        s.result.steps_taken = threshold;
+        assert_eq!(validate(&s).unwrap(), vec![]);
+        s.result.steps_taken = threshold + 1;
+        assert_eq!(validate(&s).unwrap(), vec![Flag::NearStepLimit]);
+    }
+
+    #[test]
+    fn burger_flags() {
+        let mut s = submission();
+        let limit = max_burgers(s.result.steps_taken);
+        s.result.robot_burgers = limit;
+        assert_eq!(validate(&s).unwrap(), vec![Flag::BurgersAtLimit]);
+
+        // a smaller kitchen is faster, so more is suspicious but not impossible
+        s.result.human_burgers = limit + 1;
+        assert_eq!(validate(&s).unwrap(), vec![Flag::BurgersAboveLimit]);
+    }
+
+    #[test]
+    fn short_sessions_are_flagged() {
+        let mut s = submission();
+        s.submitted_at = s.started_at + GAME_DURATION.as_millis() as u64 - 1;
+        assert_eq!(validate(&s).unwrap(), vec![Flag::ShortSession]);
+
+        // v1 submissions carry no timestamps
+        s.started_at = 0;
+        s.submitted_at = 0;
+        assert_eq!(validate(&s).unwrap(), vec![]);
+    }
+}
@@ -1,9 +1,16 @@
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+use bevy::asset::{Asset, AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
-use bevy::{prelude::*, utils::HashMap};
+use bevy::utils::BoxedFuture;
 use bevy_asset_loader::prelude::*;
 use serde::Deserialize;
+use study_game_logic::{
+    synthesize, validate, BinaryError, KitchenLayout, Position, StationKind, Strategy, SynthGame,
+};
+use study_shared_types::AdviserMode;
 
-use crate::study::components::NextMove;
 use crate::{study::components::TileType, AppState};
 
 #[derive(AssetCollection)]
@@ -40,6 +47,24 @@ pub struct MapAssets {
     pub delivery_indicator: Handle<Image>,
 }
 
+impl MapAssets {
+    /// Counters without a sprite of their own are drawn as default tiles.
+    pub fn counter(&self, kind: &StationKind) -> Handle<Image> {
+        let name = match kind {
+            StationKind::Ingredient(name) => name.as_str(),
+            StationKind::Delivery => return self.delivery.clone(),
+        };
+        match name {
+            "buns" => self.buns.clone(),
+            "lettuce" => self.lettuce.clone(),
+            "patty" => self.patty.clone(),
+            "sauce" => self.sauce.clone(),
+            "tomato" => self.tomato.clone(),
+            _ => self.default.clone(),
+        }
+    }
+}
+
 #[derive(AssetCollection)]
 pub struct CharacterAssets {
     #[asset(path = "sprites/person.png")]
@@ -60,16 +85,6 @@ pub struct AdviserAssets {
     pub arrow: Handle<Image>,
     #[asset(path = "sprites/cross.png")]
     pub cross: Handle<Image>,
-    #[asset(path = "sprites/burger_patty.png")]
-    pub patty: Handle<Image>,
-    #[asset(path = "sprites/burger_buns.png")]
-    pub buns: Handle<Image>,
-    #[asset(path = "sprites/burger_tomato.png")]
-    pub tomato: Handle<Image>,
-    #[asset(path = "sprites/burger_sauce.png")]
-    pub sauce: Handle<Image>,
-    #[asset(path = "sprites/burger_lettuce.png")]
-    pub lettuce: Handle<Image>,
     #[asset(path = "sprites/move_idle.png")]
     pub move_idle: Handle<Image>,
     #[asset(path = "sprites/move_up.png")]
@@ -106,234 +121,253 @@ pub struct BurgerUiAssets {
     pub lettuce: Handle<Image>,
 }
 
-#[derive(Deserialize, TypeUuid, Debug)]
-#[uuid = "67c1c60e-2072-469a-8129-a46c8d1f80f2"]
-pub struct TileData {
-    pub floor: Vec<[usize; 2]>,
-    pub patty: Vec<[usize; 2]>,
-    pub buns: Vec<[usize; 2]>,
-    pub tomato: Vec<[usize; 2]>,
-    pub sauce: Vec<[usize; 2]>,
-    pub lettuce: Vec<[usize; 2]>,
-    pub delivery: Vec<[usize; 2]>,
-}
-
-impl TileData {
-    pub fn tile_by_coord(&self, x: usize, y: usize) -> TileType {
-        if self.floor.contains(&[x, y]) {
-            return TileType::Floor;
-        }
-        if self.patty.contains(&[x, y]) {
-            return TileType::Patty;
-        }
-        if self.buns.contains(&[x, y]) {
-            return TileType::Buns;
-        }
-        if self.tomato.contains(&[x, y]) {
-            return TileType::Tomato;
-        }
-        if self.sauce.contains(&[x, y]) {
-            return TileType::Sauce;
-        }
-        if self.lettuce.contains(&[x, y]) {
-            return TileType::Lettuce;
-        }
-        if self.delivery.contains(&[x, y]) {
-            return TileType::Delivery;
+impl BurgerUiAssets {
+    /// Ingredients without a sprite of their own are drawn as plain squares.
+    pub fn ingredient(&self, name: &str, taken: bool) -> Handle<Image> {
+        let (active, inactive) = match name {
+            "buns" => (&self.buns, &self.buns_inactive),
+            "lettuce" => (&self.lettuce, &self.lettuce_inactive),
+            "patty" => (&self.patty, &self.patty_inactive),
+            "sauce" => (&self.sauce, &self.sauce_inactive),
+            "tomato" => (&self.tomato, &self.tomato_inactive),
+            _ => return Handle::default(),
+        };
+        if taken {
+            active.clone()
+        } else {
+            inactive.clone()
         }
-
-        TileType::Default
     }
 }
 
-pub type GraphState = (String, String, String);
-pub type Guards = Vec<String>;
+// The game logic does not know about bevy, so its data is wrapped to be loaded as assets.
 
 #[derive(Deserialize, TypeUuid, Debug)]
-#[uuid = "58aa3298-015d-421d-b7d6-fa62a441f7f5"]
-pub struct Strategy {
-    pub strat: HashMap<String, String>,
-    pub safety_adv: Vec<(GraphState, Guards)>,
-    pub fairness_adv: Vec<(GraphState, Guards)>,
-    pub guard_ap: Vec<String>,
-}
+#[serde(transparent)]
+#[uuid = "67c1c60e-2072-469a-8129-a46c8d1f80f2"]
+pub struct LayoutAsset(pub KitchenLayout);
 
-impl Strategy {
-    pub fn next_move(&self, state: &GraphState) -> Option<NextMove> {
-        let state_string: String = format!("(\'{}\', \'{}\')", state.0, state.1);
-
-        if let Some(move_string) = self.strat.get(&state_string) {
-            match move_string.as_str() {
-                "idle" => Some(NextMove::Idle),
-                "up" => Some(NextMove::Up),
-                "left" => Some(NextMove::Left),
-                "right" => Some(NextMove::Right),
-                "down" => Some(NextMove::Down),
-                "interact" => Some(NextMove::Interact),
-                _ => None,
-            }
-        } else {
-            None
-        }
+pub fn tile_by_coord(layout: &KitchenLayout, x: usize, y: usize) -> TileType {
+    let pos = Position { x, y };
+    if layout.is_floor(pos) {
+        return TileType::Floor;
+    }
+    match layout.counter_at(pos) {
+        Some(kind) => TileType::Counter(kind.clone()),
+        None => TileType::Default,
     }
 }
 
 #[derive(Deserialize, TypeUuid, Debug)]
+#[serde(transparent)]
+#[uuid = "58aa3298-015d-421d-b7d6-fa62a441f7f5"]
+pub struct StrategyAsset(pub Strategy);
+
+#[derive(Deserialize, TypeUuid, Debug)]
+#[serde(transparent)]
 #[uuid = "16ec115f-0c6f-4513-a2b1-7b07fedb5314"]
-pub struct SynthGame {
-    pub directed: bool,
-    pub multigraph: bool,
-    pub graph: Graph,
-    pub nodes: Vec<NodeData>,
-    pub links: Vec<LinkData>,
+pub struct SynthGameAsset(pub SynthGame);
+
+/// Assets with a binary encoding, see `study_game_logic::binary`.
+pub trait BinaryAsset: Asset + Sized {
+    fn from_binary(bytes: &[u8]) -> Result<Self, BinaryError>;
 }
 
-impl SynthGame {
-    pub fn is_accepting(&self, state: &GraphState) -> bool {
-        self.graph.acc.contains(state)
+impl BinaryAsset for StrategyAsset {
+    fn from_binary(bytes: &[u8]) -> Result<Self, BinaryError> {
+        Strategy::from_binary(bytes).map(StrategyAsset)
     }
+}
 
-    pub fn apply_robot_move(&self, cur_state: &GraphState, next_move: NextMove) -> GraphState {
-        for edge in &self.links {
-            if edge.source == *cur_state && edge.act() == next_move {
-                return edge.target.clone();
-            }
-        }
-
-        panic!("No next state found!");
+impl BinaryAsset for SynthGameAsset {
+    fn from_binary(bytes: &[u8]) -> Result<Self, BinaryError> {
+        SynthGame::from_binary(bytes).map(SynthGameAsset)
     }
+}
 
-    pub fn apply_human_obs(&self, cur_state: &GraphState, obs: &String) -> GraphState {
-        for edge in &self.links {
-            if edge.source == *cur_state
-                && edge
-                    .guards
-                    .as_ref()
-                    .expect("Not a human state")
-                    .contains(obs)
-            {
-                return edge.target.clone();
-            }
-        }
-
-        panic!("No next state found!");
-    }
+/// Loads binary assets with the given extensions, the counterpart of `JsonAssetPlugin`.
+pub struct BinaryAssetPlugin<A> {
+    extensions: Vec<&'static str>,
+    _marker: PhantomData<A>,
+}
 
-    // hacky hacky TODO:
-    // For now this works since we assume only one outgoing edge (prob = 1.0).
-    pub fn skip_prob_state(&self, prob_state: &GraphState) -> GraphState {
-        for edge in &self.links {
-            if edge.source == *prob_state {
-                return edge.target.clone();
-            }
+impl<A> BinaryAssetPlugin<A> {
+    pub fn new(extensions: &[&'static str]) -> Self {
+        BinaryAssetPlugin {
+            extensions: extensions.to_vec(),
+            _marker: PhantomData,
         }
-
-        panic!("No next state found!");
     }
+}
 
-    pub fn valid_robot_moves(&self, cur_state: &GraphState) -> Vec<NextMove> {
-        let mut valid_moves = Vec::new();
-
-        for edge in &self.links {
-            if edge.source == *cur_state {
-                valid_moves.push(edge.act())
-            }
+impl<A: BinaryAsset> Plugin for BinaryAssetPlugin<A> {
+    fn build(&self, app: &mut App) {
+        // the JSON plugin may have added the asset type already
+        if !app.world.contains_resource::<Assets<A>>() {
+            app.add_asset::<A>();
         }
-
-        valid_moves
+        app.add_asset_loader(BinaryAssetLoader::<A> {
+            extensions: self.extensions.clone(),
+            _marker: PhantomData,
+        });
     }
 }
 
-#[derive(Deserialize, Debug)]
-pub struct Graph {
-    pub acc: Vec<GraphState>,
-    pub init: GraphState,
-    pub human_ap: Vec<String>,
-    pub mdp_ap: Vec<String>,
+struct BinaryAssetLoader<A> {
+    extensions: Vec<&'static str>,
+    _marker: PhantomData<A>,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct NodeData {
-    pub player: u8,
-    pub ap: Option<String>,
-    pub id: GraphState,
-}
+impl<A: BinaryAsset> AssetLoader for BinaryAssetLoader<A> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let asset = A::from_binary(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
 
-#[derive(Deserialize, Debug)]
-pub struct LinkData {
-    pub act: Option<String>,
-    pub guards: Option<Guards>,
-    pub prob: Option<f32>,
-    pub source: GraphState,
-    pub target: GraphState,
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
 }
 
-impl LinkData {
-    pub fn act(&self) -> NextMove {
-        match self.act.as_ref().expect("Edge without act label!").as_str() {
-            "idle" => NextMove::Idle,
-            "up" => NextMove::Up,
-            "down" => NextMove::Down,
-            "left" => NextMove::Left,
-            "right" => NextMove::Right,
-            "interact" => NextMove::Interact,
-            _ => panic!("Edge with invalid act label!"),
-        }
+/// Set if the strategy is synthesized once game and layout are loaded, instead of loaded.
+pub struct SynthesizeStrategy;
+
+/// The strategy is synthesized unless the shipped one is asked for, to fall back on or to
+/// compare with: `?strategy=shipped` in the page URL, or `STUDY_STRATEGY=shipped` in native
+/// mode.
+fn shipped_strategy_requested() -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::env::var("STUDY_STRATEGY").as_deref() == Ok("shipped")
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        crate::backend::page_query_param("strategy").as_deref() == Some("shipped")
     }
 }
 
 pub fn setup_json(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let tile_handle: Handle<TileData> = asset_server.load("data/tiles.json.tiles");
-    commands.insert_resource(tile_handle);
-    let strat_handle: Handle<Strategy> = asset_server.load("data/strat.json.strat");
-    commands.insert_resource(strat_handle);
-    let game_handle: Handle<SynthGame> = asset_server.load("data/game.json.game");
+    let layout_handle: Handle<LayoutAsset> = asset_server.load("data/tiles.json.tiles");
+    commands.insert_resource(layout_handle);
+    if shipped_strategy_requested() {
+        // generated from the json files with `convert_assets` of study_game_logic
+        let strat_handle: Handle<StrategyAsset> = asset_server.load("data/strat.bin.strat");
+        commands.insert_resource(strat_handle);
+    } else {
+        commands.insert_resource(SynthesizeStrategy);
+    }
+    let game_handle: Handle<SynthGameAsset> = asset_server.load("data/game.bin.game");
     commands.insert_resource(game_handle);
 }
 
-pub fn load_tile_data(
+/// The sprites of the layout's atomic propositions by name, see `Proposition::sprite`.
+pub struct PropositionSprites(pub HashMap<String, Handle<Image>>);
+
+pub fn load_layout_data(
     mut commands: Commands,
-    tile_handle: Res<Handle<TileData>>,
-    mut tile_asset: ResMut<Assets<TileData>>,
+    layout_handle: Res<Handle<LayoutAsset>>,
+    mut layout_asset: ResMut<Assets<LayoutAsset>>,
+    asset_server: Res<AssetServer>,
 ) {
-    if let Some(tile_data) = tile_asset.remove(tile_handle.id) {
-        commands.insert_resource(tile_data);
+    if let Some(layout_data) = layout_asset.remove(layout_handle.id) {
+        let layout = layout_data.0;
+        let sprites = layout
+            .propositions
+            .iter()
+            .filter_map(|p| Some((p.name.clone(), asset_server.load(p.sprite.as_deref()?))))
+            .collect();
+        commands.insert_resource(PropositionSprites(sprites));
+        commands.insert_resource(Arc::new(layout));
     }
 }
 
 pub fn load_strat_data(
     mut commands: Commands,
-    strat_handle: Res<Handle<Strategy>>,
-    mut strat_asset: ResMut<Assets<Strategy>>,
+    strat_handle: Option<Res<Handle<StrategyAsset>>>,
+    mut strat_asset: ResMut<Assets<StrategyAsset>>,
 ) {
+    let strat_handle = match strat_handle {
+        Some(handle) => handle,
+        None => return,
+    };
     if let Some(strat_data) = strat_asset.remove(strat_handle.id) {
-        commands.insert_resource(strat_data);
+        commands.insert_resource(Arc::new(strat_data.0));
     }
 }
 
-#[derive(Debug)]
-pub struct SynthGameState(pub GraphState);
+pub fn synthesize_strategy(
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    requested: Option<Res<SynthesizeStrategy>>,
+    layout: Option<Res<Arc<KitchenLayout>>>,
+    synth_game: Option<Res<Arc<SynthGame>>>,
+) {
+    if let (Some(_), Some(layout), Some(synth_game)) = (requested, layout, synth_game) {
+        commands.remove_resource::<SynthesizeStrategy>();
+        match synthesize(&synth_game, &layout) {
+            Ok(strategy) => {
+                info!(
+                    "Synthesized a strategy for {} robot states",
+                    strategy.strat.len()
+                );
+                commands.insert_resource(Arc::new(strategy));
+            }
+            Err(e) => {
+                error!("invalid game data: {}", e);
+                commands.insert_resource(InvalidData(vec![e.to_string()]));
+                state
+                    .set(AppState::InvalidData)
+                    .expect("Could not change state.");
+            }
+        }
+    }
+}
 
 pub fn load_game_data(
     mut commands: Commands,
-    game_handle: Res<Handle<SynthGame>>,
-    mut game_asset: ResMut<Assets<SynthGame>>,
+    game_handle: Res<Handle<SynthGameAsset>>,
+    mut game_asset: ResMut<Assets<SynthGameAsset>>,
 ) {
     if let Some(game_data) = game_asset.remove(game_handle.id) {
-        commands.insert_resource(SynthGameState(game_data.graph.init.clone()));
-        commands.insert_resource(game_data);
+        commands.insert_resource(Arc::new(game_data.0));
     }
 }
 
+/// The problems found in the loaded game data, shown instead of the study.
+pub struct InvalidData(pub Vec<String>);
+
 pub fn finish_loading(
+    mut commands: Commands,
     mut state: ResMut<State<AppState>>,
-    tile_data: Option<Res<TileData>>,
-    strategy: Option<Res<Strategy>>,
-    synth_game: Option<Res<SynthGame>>,
+    layout: Option<Res<Arc<KitchenLayout>>>,
+    strategy: Option<Res<Arc<Strategy>>>,
+    synth_game: Option<Res<Arc<SynthGame>>>,
+    adviser_mode: Option<Res<AdviserMode>>,
 ) {
-    if tile_data.is_some() && strategy.is_some() && synth_game.is_some() {
+    if let (Some(layout), Some(strategy), Some(synth_game), Some(_)) =
+        (layout, strategy, synth_game, adviser_mode)
+    {
+        let errors = validate(&synth_game, &strategy, &layout);
+        if errors.is_empty() {
+            state
+                .set(AppState::MenuStart)
+                .expect("Could not change state.");
+            return;
+        }
+
+        for e in &errors {
+            error!("invalid game data: {}", e);
+        }
+        commands.insert_resource(InvalidData(errors.iter().map(|e| e.to_string()).collect()));
         state
-            .set(AppState::MenuStart)
+            .set(AppState::InvalidData)
             .expect("Could not change state.");
     }
 }
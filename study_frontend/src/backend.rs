@@ -0,0 +1,159 @@
+/// Used when neither the page nor `config.json` name a backend.
+#[cfg(target_arch = "wasm32")]
+const DEFAULT_BACKEND_URL: &str = "https://study.gschup.dev";
+/// Served next to the page, e.g. `{ "backend_url": "http://127.0.0.1:3030" }`.
+#[cfg(target_arch = "wasm32")]
+const CONFIG_PATH: &str = "config.json";
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static BACKEND_URL: std::cell::RefCell<Option<String>> = std::cell::RefCell::new(None);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[derive(serde::Deserialize)]
+struct FrontendConfig {
+    backend_url: String,
+}
+
+/// Build identifier sent along with the results, set via `STUDY_BUILD_HASH` at compile time.
+pub const CLIENT_BUILD: &str = match option_env!("STUDY_BUILD_HASH") {
+    Some(hash) => hash,
+    None => "dev",
+};
+
+/// Wall clock time in unix milliseconds.
+pub fn unix_millis() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now() as u64
+    }
+}
+
+/// Reads a parameter from the query string of the page the study runs in.
+#[cfg(target_arch = "wasm32")]
+pub fn page_query_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(name)
+}
+
+/// Fetches the config served next to the page, if there is one.
+#[cfg(target_arch = "wasm32")]
+async fn fetch_config() -> Option<FrontendConfig> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::Response;
+
+    let window = web_sys::window()?;
+    let resp: Response = JsFuture::from(window.fetch_with_str(CONFIG_PATH))
+        .await
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    if !resp.ok() {
+        return None;
+    }
+    let text = JsFuture::from(resp.text().ok()?).await.ok()?.as_string()?;
+    match serde_json::from_str(&text) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            bevy::log::error!("Could not parse {}: {}", CONFIG_PATH, e);
+            None
+        }
+    }
+}
+
+/// The backend to talk to: `?backend=...` in the page URL, then `config.json`, then the default.
+/// Looked up once and remembered for the rest of the session.
+#[cfg(target_arch = "wasm32")]
+async fn backend_url() -> String {
+    if let Some(url) = BACKEND_URL.with(|u| u.borrow().clone()) {
+        return url;
+    }
+
+    let url = match page_query_param("backend") {
+        Some(url) => url,
+        None => match fetch_config().await {
+            Some(config) => config.backend_url,
+            None => DEFAULT_BACKEND_URL.to_owned(),
+        },
+    };
+    let url = url.trim_end_matches('/').to_owned();
+    bevy::log::info!("Using backend {}", url);
+    BACKEND_URL.with(|u| *u.borrow_mut() = Some(url.clone()));
+    url
+}
+
+/// Sends a request to the study backend and returns the status code and response body.
+#[cfg(target_arch = "wasm32")]
+pub async fn fetch(
+    method: &str,
+    path: &str,
+    body: Option<String>,
+) -> Result<(u16, String), wasm_bindgen::JsValue> {
+    fetch_with_headers(method, path, body, &[]).await
+}
+
+/// Like `fetch`, with additional request headers.
+#[cfg(target_arch = "wasm32")]
+pub async fn fetch_with_headers(
+    method: &str,
+    path: &str,
+    body: Option<String>,
+    extra_headers: &[(&str, &str)],
+) -> Result<(u16, String), wasm_bindgen::JsValue> {
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::{Headers, Request, RequestInit, RequestMode, Response};
+
+    let headers = Headers::new()?;
+    headers.set("content-type", "application/json")?;
+    for (name, value) in extra_headers {
+        headers.set(name, value)?;
+    }
+    let mut opts = RequestInit::new();
+    opts.method(method)
+        .mode(RequestMode::Cors)
+        .headers(&headers);
+    if let Some(body) = body {
+        opts.body(Some(&JsValue::from_str(&body)));
+    }
+    let url = format!("{}/{}", backend_url().await, path);
+    let request = Request::new_with_str_and_init(&url, &opts)?;
+
+    let window = web_sys::window().expect("There should be a window.");
+    let resp: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await?
+        .dyn_into()?;
+    let text = JsFuture::from(resp.text()?)
+        .await?
+        .as_string()
+        .unwrap_or_default();
+
+    Ok((resp.status(), text))
+}
+
+/// Resolves after `duration`, without blocking the browser.
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(duration: std::time::Duration) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        if let Some(window) = web_sys::window() {
+            let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                &resolve,
+                duration.as_millis() as i32,
+            );
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
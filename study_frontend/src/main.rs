@@ -1,13 +1,16 @@
 mod assets;
+mod backend;
 mod menu;
+mod session;
 mod study;
+mod submission;
 
 use assets::*;
 use bevy::{prelude::*, render::texture::ImageSettings};
 use bevy_asset_loader::prelude::*;
 use bevy_common_assets::json::JsonAssetPlugin;
+use session::*;
 use study::{logic_systems::*, ui_systems::*};
-use study_shared_types::AdviserMode;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum AppState {
@@ -16,6 +19,8 @@ pub enum AppState {
     MenuStart,
     Study,
     End,
+    /// the loaded game data does not fit together
+    InvalidData,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
@@ -25,17 +30,6 @@ enum SystemSetLabels {
 }
 
 fn main() {
-    // choose adviser mode at random
-    /*
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    let adviser_mode = match rng.gen_range(0..=2) {
-        0 => AdviserMode::LeastLimiting,
-        1 => AdviserMode::NextMove,
-        _ => AdviserMode::None,
-    };
-    */
-
     App::new()
         .insert_resource(ImageSettings::default_nearest())
         .insert_resource(ClearColor(Color::BLACK))
@@ -44,11 +38,12 @@ fn main() {
             width: 1366.0,
             ..default()
         })
-        .insert_resource(AdviserMode::LeastLimiting)
         .add_plugins(DefaultPlugins)
-        .add_plugin(JsonAssetPlugin::<TileData>::new(&["json.tiles"]))
-        .add_plugin(JsonAssetPlugin::<Strategy>::new(&["json.strat"]))
-        .add_plugin(JsonAssetPlugin::<SynthGame>::new(&["json.game"]))
+        .add_plugin(JsonAssetPlugin::<LayoutAsset>::new(&["json.tiles"]))
+        .add_plugin(JsonAssetPlugin::<StrategyAsset>::new(&["json.strat"]))
+        .add_plugin(JsonAssetPlugin::<SynthGameAsset>::new(&["json.game"]))
+        .add_plugin(BinaryAssetPlugin::<StrategyAsset>::new(&["bin.strat"]))
+        .add_plugin(BinaryAssetPlugin::<SynthGameAsset>::new(&["bin.game"]))
         .add_loading_state(
             LoadingState::new(AppState::AssetLoading)
                 .continue_to_state(AppState::JsonLoading)
@@ -60,15 +55,26 @@ fn main() {
                 .with_collection::<BurgerUiAssets>(),
         )
         .add_state(AppState::AssetLoading)
-        // json loading
-        .add_system_set(SystemSet::on_enter(AppState::JsonLoading).with_system(setup_json))
+        // results a previous visit could not send
+        .add_startup_system(submission::resend_pending)
+        // json loading and adviser mode assignment
+        .add_system_set(
+            SystemSet::on_enter(AppState::JsonLoading)
+                .with_system(setup_json)
+                .with_system(request_session),
+        )
         .add_system_set(
             SystemSet::on_update(AppState::JsonLoading)
-                .with_system(load_tile_data)
+                .with_system(receive_session)
+                .with_system(load_layout_data)
                 .with_system(load_strat_data)
                 .with_system(load_game_data)
+                .with_system(synthesize_strategy)
                 .with_system(finish_loading),
         )
+        .add_system_set(
+            SystemSet::on_enter(AppState::InvalidData).with_system(menu::error::setup_ui),
+        )
         // start menu
         .add_system_set(SystemSet::on_enter(AppState::MenuStart).with_system(menu::start::setup_ui))
         .add_system_set(
@@ -93,14 +99,10 @@ fn main() {
                 .label(SystemSetLabels::StudyLogic)
                 .with_system(tick_timers)
                 .with_system(prepare_human_move)
-                .with_system(prepare_robot_move)
-                .with_system(
-                    resolve_moves
-                        .after(prepare_human_move)
-                        .after(prepare_robot_move),
-                )
+                .with_system(resolve_moves.after(prepare_human_move))
+                .with_system(update_animation_state.after(resolve_moves))
                 .with_system(update_animation_state.after(resolve_moves))
-                .with_system(update_animation_state.after(resolve_moves)),
+                .with_system(send_step_batches.after(resolve_moves)),
         )
         .add_system_set(
             SystemSet::on_update(AppState::Study)
@@ -131,7 +133,11 @@ fn main() {
         .add_system_set(
             SystemSet::on_enter(AppState::End)
                 .with_system(menu::end::setup_ui)
-                .with_system(menu::end::send_study_data),
+                .with_system(menu::end::send_study_data)
+                .with_system(menu::end::send_remaining_steps),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::End).with_system(menu::end::update_submission_status),
         )
         .run();
 }
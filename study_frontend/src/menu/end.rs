@@ -1,10 +1,18 @@
 use bevy::prelude::*;
-use study_shared_types::GameResults;
+use study_shared_types::{GameResults, ResultSubmission, SessionInfo, StepBatch};
 
-use crate::FontAssets;
+use crate::{
+    backend,
+    study::components::{StepLog, StudyStarted},
+    submission::{self, SubmissionState, SubmissionStatus},
+    FontAssets,
+};
 
 use super::BUTTON_TEXT;
 
+#[derive(Component)]
+pub struct SubmissionStatusText;
+
 pub fn setup_ui(mut commands: Commands, font_assets: Res<FontAssets>, result: Res<GameResults>) {
     // ui camera
     commands.spawn_bundle(Camera2dBundle::default());
@@ -66,49 +74,81 @@ pub fn setup_ui(mut commands: Commands, font_assets: Res<FontAssets>, result: Re
                 },
                 ..Default::default()
             });
+
+            // submission status
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        align_self: AlignSelf::Center,
+                        justify_content: JustifyContent::Center,
+                        margin: UiRect::all(Val::Px(20.)),
+                        ..Default::default()
+                    },
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: font_assets.default_font.clone(),
+                            font_size: 25.0,
+                            color: BUTTON_TEXT,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(SubmissionStatusText);
         });
 }
 
-pub fn send_study_data(result: Res<GameResults>) {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        warn!("Not sending any data in native mode.");
-        info!("Results: {:?}", result)
-    }
-    #[cfg(target_arch = "wasm32")]
-    {
-        use wasm_bindgen::{JsCast, JsValue};
-        use wasm_bindgen_futures::{spawn_local, JsFuture};
-        use web_sys::{Headers, Request, RequestInit, RequestMode, Response};
-
-        //const DATABASE_BACKEND_URL: &str = "http://127.0.0.1:3030/data";
-        const DATABASE_BACKEND_URL: &str = "https://study.gschup.dev/data";
+pub fn send_study_data(
+    mut commands: Commands,
+    result: Res<GameResults>,
+    started: Res<StudyStarted>,
+) {
+    let submission = ResultSubmission::new(
+        result.clone(),
+        backend::CLIENT_BUILD,
+        started.0,
+        backend::unix_millis(),
+    );
+    commands.insert_resource(submission::submit(submission));
+}
 
-        info!("Sending Study Results...");
+pub fn update_submission_status(
+    status: Option<Res<SubmissionStatus>>,
+    mut text_query: Query<&mut Text, With<SubmissionStatusText>>,
+) {
+    let state = match status {
+        Some(s) => s.0.lock().expect("Submission status poisoned.").clone(),
+        None => return,
+    };
+    let message = match state {
+        SubmissionState::Sending => "Submitting your results...".to_owned(),
+        SubmissionState::Retrying(_) => {
+            "Could not reach the server, retrying... Please keep this page open.".to_owned()
+        }
+        SubmissionState::Sent => "Your results were submitted.".to_owned(),
+        SubmissionState::Rejected(code) => format!(
+            "Your results could not be submitted (error {}). Please mention your ID in the questionnaire.",
+            code
+        ),
+        SubmissionState::Unsent => {
+            "Your results could not be submitted yet. They will be sent again when you reload this page."
+                .to_owned()
+        }
+    };
 
-        // create request - hacky json encoding
-        let result_json = result.to_json();
-        let body = JsValue::from_str(&result_json);
-        let headers = Headers::new().unwrap();
-        headers.set("content-type", "application/json").unwrap();
-        let mut opts = RequestInit::new();
-        opts.method("POST")
-            .mode(RequestMode::Cors)
-            .body(Some(&body))
-            .headers(&headers);
-        let request = Request::new_with_str_and_init(DATABASE_BACKEND_URL, &opts).unwrap();
+    for mut text in &mut text_query {
+        if text.sections[0].value != message {
+            text.sections[0].value = message.clone();
+        }
+    }
+}
 
-        // send request
-        let window = web_sys::window().unwrap();
-        spawn_local(async move {
-            match JsFuture::from(window.fetch_with_request(&request)).await {
-                Ok(resp_value) => {
-                    assert!(resp_value.is_instance_of::<Response>());
-                    let _resp: Response = resp_value.dyn_into().unwrap();
-                    // TODO: do something with the response
-                }
-                Err(e) => error!("Could not send results: {:?}", e),
-            }
+pub fn send_remaining_steps(mut step_log: ResMut<StepLog>, session: Res<SessionInfo>) {
+    if !step_log.0.is_empty() {
+        submission::send_steps(StepBatch {
+            participant_id: session.participant_id,
+            session_token: session.session_token.clone(),
+            events: std::mem::take(&mut step_log.0),
         });
     }
 }
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+use crate::{assets::InvalidData, FontAssets};
+
+use super::BUTTON_TEXT;
+
+/// More would not fit on the screen, the browser console has all of them.
+const MAX_SHOWN: usize = 15;
+
+pub fn setup_ui(mut commands: Commands, font_assets: Res<FontAssets>, invalid: Res<InvalidData>) {
+    // ui camera
+    commands.spawn_bundle(Camera2dBundle::default());
+
+    let mut problems: String = invalid
+        .0
+        .iter()
+        .take(MAX_SHOWN)
+        .map(|e| format!("{}\n", e))
+        .collect();
+    if invalid.0.len() > MAX_SHOWN {
+        problems.push_str(&format!("... and {} more\n", invalid.0.len() - MAX_SHOWN));
+    }
+
+    // root node
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect::all(Val::Px(0.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_content: AlignContent::Center,
+                align_items: AlignItems::Center,
+                align_self: AlignSelf::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    align_self: AlignSelf::Center,
+                    justify_content: JustifyContent::Center,
+                    max_size: Size::new(Val::Percent(90.0), Val::Undefined),
+                    ..Default::default()
+                },
+                text: Text {
+                    sections: vec![
+                        TextSection {
+                            value: "The study could not be started, its game data is invalid:\n\n"
+                                .to_owned(),
+                            style: TextStyle {
+                                font: font_assets.default_font.clone(),
+                                font_size: 40.0,
+                                color: Color::RED,
+                            },
+                        },
+                        TextSection {
+                            value: problems,
+                            style: TextStyle {
+                                font: font_assets.default_font.clone(),
+                                font_size: 20.0,
+                                color: BUTTON_TEXT,
+                            },
+                        },
+                    ],
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        });
+}
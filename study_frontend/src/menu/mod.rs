@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 
 pub mod end;
+pub mod error;
 pub mod start;
 
 pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
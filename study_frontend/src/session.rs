@@ -0,0 +1,112 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use study_shared_types::SessionInfo;
+
+pub const SESSION_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+pub enum SessionRequest {
+    Pending,
+    Done(SessionInfo),
+    Failed,
+}
+
+/// Shared between the loading systems and the async request, which cannot insert resources itself.
+pub struct SessionSlot(pub Arc<Mutex<SessionRequest>>);
+
+pub struct SessionRetryTimer(pub Timer);
+
+pub fn request_session(mut commands: Commands) {
+    let slot = Arc::new(Mutex::new(SessionRequest::Pending));
+    send_session_request(slot.clone());
+    commands.insert_resource(SessionSlot(slot));
+    commands.insert_resource(SessionRetryTimer(Timer::new(SESSION_RETRY_DELAY, false)));
+}
+
+pub fn receive_session(
+    mut commands: Commands,
+    slot: Option<Res<SessionSlot>>,
+    mut retry_timer: ResMut<SessionRetryTimer>,
+    time: Res<Time>,
+) {
+    let slot = if let Some(s) = slot {
+        s
+    } else {
+        return;
+    };
+
+    let mut request = slot.0.lock().expect("Session slot poisoned.");
+    match &*request {
+        SessionRequest::Pending => (),
+        SessionRequest::Done(session) => {
+            info!(
+                "Participant {} assigned adviser mode: {:?}",
+                session.participant_id, session.adviser_mode
+            );
+            commands.insert_resource(session.adviser_mode);
+            commands.insert_resource(session.clone());
+            commands.remove_resource::<SessionSlot>();
+            commands.remove_resource::<SessionRetryTimer>();
+        }
+        SessionRequest::Failed => {
+            // the study cannot start without an assignment, so keep asking
+            retry_timer.0.tick(time.delta());
+            if retry_timer.0.finished() {
+                retry_timer.0.reset();
+                *request = SessionRequest::Pending;
+                send_session_request(slot.0.clone());
+            }
+        }
+    }
+}
+
+fn send_session_request(slot: Arc<Mutex<SessionRequest>>) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use rand::Rng;
+        use study_shared_types::AdviserMode;
+
+        warn!("Not requesting a session in native mode.");
+        *slot.lock().expect("Session slot poisoned.") = SessionRequest::Done(SessionInfo {
+            participant_id: rand::thread_rng().gen_range(100000..999999),
+            session_token: String::new(),
+            adviser_mode: AdviserMode::LeastLimiting,
+        });
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        use wasm_bindgen_futures::spawn_local;
+
+        info!("Requesting Session...");
+
+        // pilot runs are started from a link with `?pilot=true` and kept apart in the data
+        let path = match crate::backend::page_query_param("pilot").as_deref() {
+            Some("true") => "session?pilot=true",
+            _ => "session",
+        };
+
+        spawn_local(async move {
+            let result = match crate::backend::fetch("POST", path, None).await {
+                Ok((200..=299, body)) => match serde_json::from_str::<SessionInfo>(&body) {
+                    Ok(session) => SessionRequest::Done(session),
+                    Err(e) => {
+                        error!("Could not parse session: {}", e);
+                        SessionRequest::Failed
+                    }
+                },
+                Ok((status, _)) => {
+                    error!("Session request rejected with status {}", status);
+                    SessionRequest::Failed
+                }
+                Err(e) => {
+                    error!("Could not request session: {:?}", e);
+                    SessionRequest::Failed
+                }
+            };
+            *slot.lock().expect("Session slot poisoned.") = result;
+        });
+    }
+}
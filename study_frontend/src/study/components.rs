@@ -1,6 +1,6 @@
-use std::{error::Error, fmt::Display, str::FromStr, usize};
-
 use bevy::prelude::*;
+use study_game_logic::{Actor, NextMove, Position, StationKind};
+use study_shared_types::StepEvent;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum StudyState {
@@ -36,21 +36,12 @@ pub struct HumanBurgerText;
 #[derive(Component)]
 pub struct RobotBurgerText;
 
+/// One ingredient of a recipe in the sidebar, placed on a circle with the others.
 #[derive(Component)]
-pub enum BurgerComponent {
-    Buns,
-    Patty,
-    Lettuce,
-    Tomato,
-    Sauce,
-}
-
-#[derive(Component, Debug)]
-pub enum Interact {
-    No,
-    In(Position),
-    Out(Position),
-    Stay(Position),
+pub struct BurgerComponent {
+    pub ingredient: String,
+    pub index: usize,
+    pub count: usize,
 }
 
 #[derive(Component)]
@@ -59,54 +50,16 @@ pub struct AdviserUi;
 #[derive(Component)]
 pub struct AdviserIcon;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum NextMove {
-    Idle,
-    Up,
-    Down,
-    Left,
-    Right,
-    Interact,
-}
-
-impl Default for NextMove {
-    fn default() -> Self {
-        NextMove::Idle
-    }
-}
-
-impl FromStr for NextMove {
-    type Err = ParseMoveError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "up" => Ok(NextMove::Up),
-            "down" => Ok(NextMove::Down),
-            "left" => Ok(NextMove::Left),
-            "right" => Ok(NextMove::Right),
-            "interact" => Ok(NextMove::Interact),
-            _ => Err(ParseMoveError),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct ParseMoveError;
-impl Display for ParseMoveError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Something went wrong parsing a Move!")
-    }
-}
-impl Error for ParseMoveError {}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct HumanNextMove(pub NextMove);
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct StepCounter(pub u32);
+/// Steps recorded since the last batch was sent to the backend.
+#[derive(Default, Debug)]
+pub struct StepLog(pub Vec<StepEvent>);
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct RobotNextMove(pub NextMove);
+/// Wall clock time the game started at, in unix milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct StudyStarted(pub u64);
 
 #[derive(Component, Debug, Copy, Clone)]
 pub struct Tile {
@@ -114,43 +67,28 @@ pub struct Tile {
     pub y: usize,
 }
 
-#[derive(Component, Debug, Copy, Clone)]
-pub struct Position {
-    pub x: usize,
-    pub y: usize,
-}
-
-impl Position {
-    pub fn is_equal(&self, coords: (usize, usize)) -> bool {
-        self.x == coords.0 && self.y == coords.1
-    }
-}
-
-#[derive(Component, Debug, Copy, Clone)]
-pub struct NextPosition {
-    pub x: usize,
-    pub y: usize,
+/// What is drawn of an actor. The game moves on at once, the sprite follows within one
+/// animation from `pos` to the actor's position.
+#[derive(Component, Debug, Clone)]
+pub struct ActorView {
+    pub pos: Position,
+    pub actor: Actor,
 }
 
-impl NextPosition {
-    pub fn as_pos(&self) -> Position {
-        Position {
-            x: self.x,
-            y: self.y,
+impl ActorView {
+    pub fn new(actor: Actor) -> Self {
+        ActorView {
+            pos: actor.pos,
+            actor,
         }
     }
 }
 
-#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
 pub enum TileType {
     Default,
     Floor,
-    Buns,
-    Patty,
-    Lettuce,
-    Tomato,
-    Sauce,
-    Delivery,
+    Counter(StationKind),
 }
 
 #[derive(Component)]
@@ -177,87 +115,6 @@ pub struct TimerText;
 #[derive(Component)]
 pub struct BurgerText;
 
-#[derive(Default, Debug, Copy, Clone, Component)]
-pub struct BurgerProgress {
-    pub assembled: u32,
-    pub buns: bool,
-    pub patty: bool,
-    pub lettuce: bool,
-    pub tomato: bool,
-    pub sauce: bool,
-}
-
-impl BurgerProgress {
-    pub fn ready(&self) -> bool {
-        self.buns && self.patty && self.lettuce && self.tomato && self.sauce
-    }
-
-    pub fn make_burger(&mut self) -> bool {
-        if self.ready() {
-            self.assembled += 1;
-            self.buns = false;
-            self.patty = false;
-            self.lettuce = false;
-            self.tomato = false;
-            self.sauce = false;
-            return true;
-        }
-        return false;
-    }
-
-    pub fn reset(&mut self) {
-        self.buns = false;
-        self.patty = false;
-        self.lettuce = false;
-        self.tomato = false;
-        self.sauce = false;
-    }
-}
-
-#[derive(Default, Debug)]
-pub struct ActiveAdvisers {
-    pub safety: Vec<String>,
-    pub fairness: Vec<String>,
-    pub next_move: NextMove,
-}
-
-impl ActiveAdvisers {
-    pub fn clear_all(&mut self) {
-        self.safety.clear();
-        self.fairness.clear();
-    }
-
-    pub fn safety_violated(&self, obs: &String) -> bool {
-        for guard in &self.safety {
-            if obs_match_guard(obs, guard) {
-                return true;
-            }
-        }
-        false
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.safety.is_empty() && self.fairness.is_empty()
-    }
-}
-
-fn obs_match_guard(obs: &String, guard: &String) -> bool {
-    assert_eq!(obs.len(), guard.len());
-    for (i, c) in guard.chars().enumerate() {
-        if c == 'X' {
-            continue;
-        }
-        if c != obs
-            .chars()
-            .nth(i)
-            .expect("obs_match_guard: obs and guard should be same length!")
-        {
-            return false;
-        }
-    }
-    true
-}
-
 pub struct SafetyViolated;
 
 #[derive(Component)]
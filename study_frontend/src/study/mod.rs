@@ -1,11 +1,13 @@
 use std::time::Duration;
 
+use bevy::prelude::Vec2;
+use study_game_logic::KitchenLayout;
+
 pub mod components;
 pub mod logic_systems;
 pub mod ui_systems;
 
 pub const TILE_PADDING: f32 = 60.0;
-pub const NUM_TILES: usize = 5;
 
 pub const MENU_Z: f32 = 10.0;
 pub const SIDEBAR_WIDTH: f32 = 300.0;
@@ -14,23 +16,24 @@ pub const SIDEBAR_PADDING: f32 = 50.0;
 pub const SPEECH_BUBBLE_Z: f32 = MENU_Z + 10.;
 pub const ADVISER_SIZE: f32 = 75.0;
 
-pub const ANIM_DURATION: Duration = Duration::from_millis(400);
+pub use study_shared_types::{ANIM_DURATION, GAME_DURATION};
 pub const FADE_DURATION: Duration = Duration::from_millis(1000);
-pub const GAME_DURATION: Duration = Duration::from_secs(120);
-
-pub const DELIVERY_POS_H: (usize, usize) = (2, 4);
-pub const BUNS_POS_H: (usize, usize) = (1, 3);
-pub const LETTUCE_POS_H: (usize, usize) = (4, 3);
-pub const PATTY_POS_H: (usize, usize) = (0, 3);
-pub const TOMATO_POS_H: (usize, usize) = (2, 3);
-pub const SAUCE_POS_H: (usize, usize) = (3, 3);
-
-pub const DELIVERY_POS_R: (usize, usize) = (2, 0);
-pub const BUNS_POS_R: (usize, usize) = (1, 1);
-pub const LETTUCE_POS_R: (usize, usize) = (4, 1);
-pub const PATTY_POS_R: (usize, usize) = (0, 1);
-pub const TOMATO_POS_R: (usize, usize) = (2, 1);
-pub const SAUCE_POS_R: (usize, usize) = (3, 1);
-
-pub const HUMAN_START: (usize, usize) = (2, 4);
-pub const ROBOT_START: (usize, usize) = (2, 0);
+
+pub const STEP_BATCH_SIZE: usize = 50;
+
+/// The largest tiles with which the whole kitchen fits into the window.
+pub fn tile_size_for(layout: &KitchenLayout, width: f32, height: f32) -> f32 {
+    let size_min = width.min(height);
+    let tiles = layout.width().max(layout.height());
+    (size_min - (TILE_PADDING * 2.0)) / tiles as f32
+}
+
+/// The centre of a grid cell, the kitchen is centred in the window.
+pub fn tile_center(layout: &KitchenLayout, tile_size: f32, x: f32, y: f32) -> Vec2 {
+    let area_x = tile_size * layout.width() as f32 + 2.0 * TILE_PADDING;
+    let area_y = tile_size * layout.height() as f32 + 2.0 * TILE_PADDING;
+    Vec2::new(
+        TILE_PADDING + tile_size * x - area_x * 0.5 + tile_size * 0.5,
+        TILE_PADDING + tile_size * y - area_y * 0.5 + tile_size * 0.5,
+    )
+}
@@ -1,6 +1,9 @@
-use std::f32::consts::PI;
+use std::{f32::consts::PI, sync::Arc};
 
 use bevy::{prelude::*, window::WindowResized};
+use study_game_logic::{
+    counter_direction, guard_literals, Agent, Interact, KitchenGame, KitchenLayout, NextMove,
+};
 use study_shared_types::{AdviserMode, GameResults};
 
 use crate::{assets::*, study::components::*};
@@ -12,6 +15,7 @@ pub fn setup_burger_ui(
     menu_sprites: Res<MenuAssets>,
     burger_components: Res<BurgerUiAssets>,
     fonts: Res<FontAssets>,
+    layout: Res<Arc<KitchenLayout>>,
 ) {
     commands
         .spawn_bundle(SpriteBundle {
@@ -53,79 +57,25 @@ pub fn setup_burger_ui(
                     ..default()
                 })
                 .insert(RobotBurgerText);
-            // human components
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.buns_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Buns)
-                .insert(HumanBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.patty_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Patty)
-                .insert(HumanBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.lettuce_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Lettuce)
-                .insert(HumanBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.tomato_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Tomato)
-                .insert(HumanBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.sauce_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Sauce)
-                .insert(HumanBurgerUi);
-
-            // robot components
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.buns_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Buns)
-                .insert(RobotBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.patty_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Patty)
-                .insert(RobotBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.lettuce_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Lettuce)
-                .insert(RobotBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.tomato_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Tomato)
-                .insert(RobotBurgerUi);
-            parent
-                .spawn_bundle(SpriteBundle {
-                    texture: burger_components.sauce_inactive.clone(),
-                    ..default()
-                })
-                .insert(BurgerComponent::Sauce)
-                .insert(RobotBurgerUi);
+            // the ingredients of each recipe
+            for agent in [Agent::Human, Agent::Robot] {
+                let recipe = layout.recipe(agent);
+                for (index, ingredient) in recipe.ingredients.iter().enumerate() {
+                    let mut component = parent.spawn_bundle(SpriteBundle {
+                        texture: burger_components.ingredient(ingredient, false),
+                        ..default()
+                    });
+                    component.insert(BurgerComponent {
+                        ingredient: ingredient.clone(),
+                        index,
+                        count: recipe.ingredients.len(),
+                    });
+                    match agent {
+                        Agent::Human => component.insert(HumanBurgerUi),
+                        Agent::Robot => component.insert(RobotBurgerUi),
+                    };
+                }
+            }
         });
 }
 
@@ -182,51 +132,17 @@ pub fn update_human_burger_ui(
         (&mut Handle<Image>, &BurgerComponent),
         (With<HumanBurgerUi>, Without<RobotBurgerUi>),
     >,
-    progress_query: Query<&BurgerProgress, (With<Player>, Without<Robot>)>,
+    view_query: Query<&ActorView, (With<Player>, Without<Robot>)>,
     assets: Res<BurgerUiAssets>,
 ) {
-    let progress = progress_query
+    let progress = &view_query
         .get_single()
-        .expect("There should only be one human.");
+        .expect("There should only be one human.")
+        .actor
+        .progress;
 
     for (mut tex, bc) in components_h.iter_mut() {
-        match *bc {
-            BurgerComponent::Buns => {
-                *tex = if progress.buns {
-                    assets.buns.clone()
-                } else {
-                    assets.buns_inactive.clone()
-                };
-            }
-            BurgerComponent::Patty => {
-                *tex = if progress.patty {
-                    assets.patty.clone()
-                } else {
-                    assets.patty_inactive.clone()
-                };
-            }
-            BurgerComponent::Lettuce => {
-                *tex = if progress.lettuce {
-                    assets.lettuce.clone()
-                } else {
-                    assets.lettuce_inactive.clone()
-                };
-            }
-            BurgerComponent::Tomato => {
-                *tex = if progress.tomato {
-                    assets.tomato.clone()
-                } else {
-                    assets.tomato_inactive.clone()
-                };
-            }
-            BurgerComponent::Sauce => {
-                *tex = if progress.sauce {
-                    assets.sauce.clone()
-                } else {
-                    assets.sauce_inactive.clone()
-                };
-            }
-        }
+        *tex = assets.ingredient(&bc.ingredient, progress.has(&bc.ingredient));
     }
 }
 
@@ -235,51 +151,17 @@ pub fn update_robot_burger_ui(
         (&mut Handle<Image>, &BurgerComponent),
         (With<RobotBurgerUi>, Without<HumanBurgerUi>),
     >,
-    progress_query: Query<&BurgerProgress, (With<Robot>, Without<Player>)>,
+    view_query: Query<&ActorView, (With<Robot>, Without<Player>)>,
     assets: Res<BurgerUiAssets>,
 ) {
-    let progress = progress_query
+    let progress = &view_query
         .get_single()
-        .expect("There should only be one human.");
+        .expect("There should only be one robot.")
+        .actor
+        .progress;
 
     for (mut tex, bc) in components_r.iter_mut() {
-        match *bc {
-            BurgerComponent::Buns => {
-                *tex = if progress.buns {
-                    assets.buns.clone()
-                } else {
-                    assets.buns_inactive.clone()
-                };
-            }
-            BurgerComponent::Patty => {
-                *tex = if progress.patty {
-                    assets.patty.clone()
-                } else {
-                    assets.patty_inactive.clone()
-                };
-            }
-            BurgerComponent::Lettuce => {
-                *tex = if progress.lettuce {
-                    assets.lettuce.clone()
-                } else {
-                    assets.lettuce_inactive.clone()
-                };
-            }
-            BurgerComponent::Tomato => {
-                *tex = if progress.tomato {
-                    assets.tomato.clone()
-                } else {
-                    assets.tomato_inactive.clone()
-                };
-            }
-            BurgerComponent::Sauce => {
-                *tex = if progress.sauce {
-                    assets.sauce.clone()
-                } else {
-                    assets.sauce_inactive.clone()
-                };
-            }
-        }
+        *tex = assets.ingredient(&bc.ingredient, progress.has(&bc.ingredient));
     }
 }
 
@@ -300,18 +182,10 @@ pub fn scale_burger_ingredients_ui(
         let cx_h = 0.;
         let cy_h = window_upper * 0.5;
         let radius = SIDEBAR_WIDTH * 0.5 - component_scale * 0.5 - 10.;
-        let theta = (2. * PI) / 5.;
 
         for (mut bc_transf, mut bc_sprite, bc) in human_components.iter_mut() {
             bc_sprite.custom_size = Some(Vec2::new(component_scale, component_scale));
-            let count = match *bc {
-                BurgerComponent::Buns => 0.,
-                BurgerComponent::Patty => 1.,
-                BurgerComponent::Lettuce => 2.,
-                BurgerComponent::Tomato => 3.,
-                BurgerComponent::Sauce => 4.,
-            };
-            let angle = count * theta;
+            let angle = bc.index as f32 * (2. * PI) / bc.count as f32;
             bc_transf.translation = Vec3::new(
                 cx_h + radius * angle.cos() - 10.,
                 cy_h + radius * angle.sin() - 15.,
@@ -321,14 +195,7 @@ pub fn scale_burger_ingredients_ui(
 
         for (mut bc_transf, mut bc_sprite, bc) in robot_components.iter_mut() {
             bc_sprite.custom_size = Some(Vec2::new(component_scale, component_scale));
-            let count = match *bc {
-                BurgerComponent::Buns => 0.,
-                BurgerComponent::Patty => 1.,
-                BurgerComponent::Lettuce => 2.,
-                BurgerComponent::Tomato => 3.,
-                BurgerComponent::Sauce => 4.,
-            };
-            let angle = count * theta;
+            let angle = bc.index as f32 * (2. * PI) / bc.count as f32;
             bc_transf.translation = Vec3::new(
                 cx_h + radius * angle.cos() - 10.,
                 cy_h + radius * angle.sin() - window_upper - 40.,
@@ -391,12 +258,11 @@ pub fn window_resize_listener(
     resize_event: Res<Events<WindowResized>>,
     mut tile_size: ResMut<TileSize>,
     mut window_size: ResMut<WindowSize>,
+    layout: Res<Arc<KitchenLayout>>,
 ) {
     let mut reader = resize_event.get_reader();
     for e in reader.iter(&resize_event) {
-        let size_min = e.width.min(e.height);
-        let new_tile_size = (size_min - (TILE_PADDING * 2.0)) / NUM_TILES as f32;
-        tile_size.0 = new_tile_size;
+        tile_size.0 = tile_size_for(&layout, e.width, e.height);
         window_size.width = e.width;
         window_size.height = e.height;
     }
@@ -424,15 +290,12 @@ pub fn update_burger_text(
 pub fn resize_tiles(
     tile_size: Res<TileSize>,
     mut tiles: Query<(&mut Transform, &mut Sprite, &Tile), (Without<Player>, Without<Robot>)>,
+    layout: Res<Arc<KitchenLayout>>,
 ) {
     if tile_size.is_changed() {
-        let area_size = tile_size.0 * NUM_TILES as f32 + 2.0 * TILE_PADDING;
         for (mut t, mut sprite, tile) in tiles.iter_mut() {
-            let pos_x: f32 =
-                TILE_PADDING + tile_size.0 * tile.x as f32 - area_size * 0.5 + tile_size.0 * 0.5;
-            let pos_y: f32 =
-                TILE_PADDING + tile_size.0 * tile.y as f32 - area_size * 0.5 + tile_size.0 * 0.5;
-            t.translation = Vec3::new(pos_x, pos_y, 0.);
+            let center = tile_center(&layout, tile_size.0, tile.x as f32, tile.y as f32);
+            t.translation = center.extend(0.);
             sprite.custom_size = Some(Vec2::new(tile_size.0, tile_size.0));
         }
     }
@@ -451,10 +314,11 @@ pub fn resize_delivery_indicator(
 
 pub fn toggle_delivery_indicator(
     mut arrow_vis: Query<&mut Visibility, With<DeliveryIndicator>>,
-    burger_progress: Query<&BurgerProgress, With<Player>>,
+    view: Query<&ActorView, With<Player>>,
+    layout: Res<Arc<KitchenLayout>>,
 ) {
-    let progress = burger_progress.single();
-    arrow_vis.single_mut().is_visible = progress.ready();
+    let recipe = layout.recipe(Agent::Human);
+    arrow_vis.single_mut().is_visible = view.single().actor.progress.ready(recipe);
 }
 
 pub fn resize_actors(
@@ -483,7 +347,7 @@ pub fn resize_speech_bubble(
 
 pub fn toggle_speech_bubble(
     mut bubble: Query<&mut Visibility, With<SpeechBubble>>,
-    active_advisers: Res<ActiveAdvisers>,
+    game: Res<KitchenGame>,
     adviser_mode: Res<AdviserMode>,
 ) {
     match *adviser_mode {
@@ -492,8 +356,8 @@ pub fn toggle_speech_bubble(
             bubble.single_mut().is_visible = true;
         }
         AdviserMode::LeastLimiting => {
-            if active_advisers.is_changed() {
-                bubble.single_mut().is_visible = !active_advisers.is_empty();
+            if game.is_changed() {
+                bubble.single_mut().is_visible = !game.advisers().is_empty();
             }
         }
     }
@@ -501,17 +365,29 @@ pub fn toggle_speech_bubble(
 
 pub fn update_adviser_ui(
     bubble: Query<Entity, With<SpeechBubble>>,
-    active_advisers: Res<ActiveAdvisers>,
+    shown_icons: Query<Entity, With<AdviserIcon>>,
+    game: Res<KitchenGame>,
     adviser_mode: Res<AdviserMode>,
     mut commands: Commands,
     adviser_icons: Res<AdviserAssets>,
+    proposition_sprites: Res<PropositionSprites>,
+    fonts: Res<FontAssets>,
     tile_size: Res<TileSize>,
-    synth_game: Res<SynthGame>,
 ) {
+    let active_advisers = game.advisers();
+    let human_ap = &game.synth_game().graph.human_ap;
+
+    // the advice changes with every step
+    if game.is_changed() {
+        for icon in shown_icons.iter() {
+            commands.entity(icon).despawn_recursive();
+        }
+    }
+
     match *adviser_mode {
         AdviserMode::None => (), // robot stays silent
         AdviserMode::NextMove => {
-            if active_advisers.is_changed() {
+            if game.is_changed() {
                 let bubble_id = bubble.single();
                 commands.entity(bubble_id).add_children(|parent| {
                     parent
@@ -530,7 +406,15 @@ pub fn update_adviser_ui(
                         })
                         .insert(Study)
                         .insert(AdviserIcon);
-                    let sprite_handle = match active_advisers.next_move {
+                    // interacting is shown as the key that walks into the counter
+                    let key = match active_advisers.next_move {
+                        NextMove::Interact => {
+                            counter_direction(game.layout(), Agent::Human, game.human().pos)
+                                .unwrap_or(NextMove::Interact)
+                        }
+                        next_move => next_move,
+                    };
+                    let sprite_handle = match key {
                         NextMove::Idle => adviser_icons.move_idle.clone(),
                         NextMove::Up => adviser_icons.move_up.clone(),
                         NextMove::Down => adviser_icons.move_down.clone(),
@@ -558,187 +442,153 @@ pub fn update_adviser_ui(
             }
         }
         AdviserMode::LeastLimiting => {
-            if active_advisers.is_changed() {
+            if game.is_changed() {
                 let bubble_id = bubble.single();
                 commands.entity(bubble_id).add_children(|parent| {
+                    let rows = active_advisers
+                        .safety
+                        .iter()
+                        .map(|guards| (adviser_icons.cross.clone(), guards))
+                        .chain(
+                            active_advisers
+                                .fairness
+                                .iter()
+                                .map(|guards| (adviser_icons.arrow.clone(), guards)),
+                        );
+                    let adviser_rows = AdviserRows {
+                        adviser_icons: &adviser_icons,
+                        fonts: &fonts,
+                        sprites: &proposition_sprites,
+                        layout: game.layout(),
+                        human_ap,
+                    };
                     let mut adviser_pos_y = 0.;
-                    // safety
-                    for saf_adv in &active_advisers.safety {
-                        let mut adviser_pos_x = -0.5 * tile_size.0;
-                        parent
-                            .spawn_bundle(SpriteBundle {
-                                sprite: Sprite {
-                                    custom_size: Some(Vec2::new(ADVISER_SIZE, ADVISER_SIZE)),
-                                    ..default()
-                                },
-                                transform: Transform::from_xyz(
-                                    adviser_pos_x,
-                                    adviser_pos_y,
-                                    MENU_Z + 1.,
-                                ),
-                                texture: adviser_icons.person.clone(),
-                                ..default()
-                            })
-                            .insert(Study)
-                            .insert(AdviserIcon);
-                        adviser_pos_x += ADVISER_SIZE;
-                        parent
-                            .spawn_bundle(SpriteBundle {
-                                sprite: Sprite {
-                                    custom_size: Some(Vec2::new(ADVISER_SIZE, ADVISER_SIZE)),
-                                    ..default()
-                                },
-                                transform: Transform::from_xyz(
-                                    adviser_pos_x,
-                                    adviser_pos_y,
-                                    MENU_Z + 1.,
-                                ),
-                                texture: adviser_icons.cross.clone(),
-                                ..default()
-                            })
-                            .insert(Study)
-                            .insert(AdviserIcon);
-                        adviser_pos_x += ADVISER_SIZE;
-                        for (i, c) in saf_adv.chars().enumerate() {
-                            if c == 'X' {
-                                continue;
-                            }
-                            if c == '0' {
-                                panic!("Safety adviser with negative proposition, not supported!")
-                            }
-                            let sprite_handle = match synth_game.graph.human_ap[i].as_str() {
-                                "buns_h" => adviser_icons.buns.clone(),
-                                "patty_h" => adviser_icons.patty.clone(),
-                                "tomato_h" => adviser_icons.tomato.clone(),
-                                "lettuce_h" => adviser_icons.lettuce.clone(),
-                                "ketchup_h" => adviser_icons.sauce.clone(),
-                                _ => panic!("Could not find sprite!"),
-                            };
-                            parent
-                                .spawn_bundle(SpriteBundle {
-                                    sprite: Sprite {
-                                        custom_size: Some(Vec2::new(ADVISER_SIZE, ADVISER_SIZE)),
-                                        ..default()
-                                    },
-                                    transform: Transform::from_xyz(
-                                        adviser_pos_x,
-                                        adviser_pos_y,
-                                        SPEECH_BUBBLE_Z + 1.,
-                                    ),
-                                    texture: sprite_handle,
-                                    ..default()
-                                })
-                                .insert(Study)
-                                .insert(AdviserIcon);
-                            adviser_pos_x += ADVISER_SIZE;
-                        }
+                    for (verdict, guards) in rows {
+                        let pos = Vec2::new(-0.5 * tile_size.0, adviser_pos_y);
+                        adviser_rows.spawn(parent, pos, verdict, guards);
                         adviser_pos_y -= ADVISER_SIZE;
                     }
+                });
+            }
+        }
+    }
+}
 
-                    // fairness
-                    for fair_adv in &active_advisers.fairness {
-                        let mut adviser_pos_x = -0.5 * tile_size.0;
-                        parent
-                            .spawn_bundle(SpriteBundle {
-                                sprite: Sprite {
-                                    custom_size: Some(Vec2::new(ADVISER_SIZE, ADVISER_SIZE)),
-                                    ..default()
-                                },
-                                transform: Transform::from_xyz(
-                                    adviser_pos_x,
-                                    adviser_pos_y,
-                                    SPEECH_BUBBLE_Z + 1.,
-                                ),
-                                texture: adviser_icons.person.clone(),
-                                ..default()
-                            })
-                            .insert(Study)
-                            .insert(AdviserIcon);
-                        adviser_pos_x += ADVISER_SIZE;
-                        parent
-                            .spawn_bundle(SpriteBundle {
-                                sprite: Sprite {
-                                    custom_size: Some(Vec2::new(ADVISER_SIZE, ADVISER_SIZE)),
-                                    ..default()
-                                },
-                                transform: Transform::from_xyz(
-                                    adviser_pos_x,
-                                    adviser_pos_y,
-                                    SPEECH_BUBBLE_Z + 1.,
-                                ),
-                                texture: adviser_icons.arrow.clone(),
-                                ..default()
-                            })
-                            .insert(Study)
-                            .insert(AdviserIcon);
-                        adviser_pos_x += ADVISER_SIZE;
-                        for (i, c) in fair_adv.chars().enumerate() {
-                            if c == 'X' {
-                                continue;
-                            }
-                            if c == '0' {
-                                panic!("fairness adviser with negative proposition, not supported!")
-                            }
-                            let sprite_handle = match synth_game.graph.human_ap[i].as_str() {
-                                "buns_h" => adviser_icons.buns.clone(),
-                                "patty_h" => adviser_icons.patty.clone(),
-                                "tomato_h" => adviser_icons.tomato.clone(),
-                                "lettuce_h" => adviser_icons.lettuce.clone(),
-                                "ketchup_h" => adviser_icons.sauce.clone(),
-                                _ => panic!("Could not find sprite!"),
-                            };
-                            parent
-                                .spawn_bundle(SpriteBundle {
-                                    sprite: Sprite {
-                                        custom_size: Some(Vec2::new(ADVISER_SIZE, ADVISER_SIZE)),
-                                        ..default()
-                                    },
-                                    transform: Transform::from_xyz(
-                                        adviser_pos_x,
-                                        adviser_pos_y,
-                                        SPEECH_BUBBLE_Z + 1.,
-                                    ),
-                                    texture: sprite_handle,
-                                    ..default()
-                                })
-                                .insert(Study)
-                                .insert(AdviserIcon);
-                            adviser_pos_x += ADVISER_SIZE;
-                        }
-                        adviser_pos_y -= ADVISER_SIZE;
+/// Draws the advisers of the least-limiting condition, one row each.
+struct AdviserRows<'a> {
+    adviser_icons: &'a AdviserAssets,
+    fonts: &'a FontAssets,
+    sprites: &'a PropositionSprites,
+    layout: &'a KitchenLayout,
+    human_ap: &'a [String],
+}
+
+impl AdviserRows<'_> {
+    /// The person, a cross for what they must not do or an arrow for what they should do, and
+    /// the guards separated by "or". A proposition that must not hold is crossed out, and one
+    /// without a sprite is written out.
+    fn spawn(
+        &self,
+        parent: &mut ChildBuilder,
+        pos: Vec2,
+        verdict: Handle<Image>,
+        guards: &[String],
+    ) {
+        let mut x = pos.x;
+        let z = SPEECH_BUBBLE_Z + 1.;
+        let person = self.adviser_icons.person.clone();
+        spawn_adviser_icon(parent, person, x, pos.y, z, ADVISER_SIZE);
+        x += ADVISER_SIZE;
+        spawn_adviser_icon(parent, verdict, x, pos.y, z, ADVISER_SIZE);
+        x += ADVISER_SIZE;
+        for (i, guard) in guards.iter().enumerate() {
+            if i > 0 {
+                self.spawn_text(parent, "or", x, pos.y, z);
+                x += ADVISER_SIZE;
+            }
+            for (ap, holds) in guard_literals(guard) {
+                let name = &self.human_ap[ap];
+                match self.sprites.0.get(name) {
+                    Some(sprite) => {
+                        spawn_adviser_icon(parent, sprite.clone(), x, pos.y, z, ADVISER_SIZE)
                     }
-                });
+                    None => {
+                        let label = self.layout.proposition(name).map_or(name, |p| &p.label);
+                        self.spawn_text(parent, label, x, pos.y, z);
+                    }
+                }
+                if !holds {
+                    let cross = self.adviser_icons.cross.clone();
+                    spawn_adviser_icon(parent, cross, x, pos.y, z + 1., 0.5 * ADVISER_SIZE);
+                }
+                x += ADVISER_SIZE;
             }
         }
     }
+
+    fn spawn_text(&self, parent: &mut ChildBuilder, text: &str, x: f32, y: f32, z: f32) {
+        parent
+            .spawn_bundle(Text2dBundle {
+                transform: Transform::from_xyz(x, y, z),
+                text: Text::from_section(
+                    text,
+                    TextStyle {
+                        font: self.fonts.default_font.clone(),
+                        font_size: 0.5 * ADVISER_SIZE,
+                        color: Color::BLACK,
+                    },
+                )
+                .with_alignment(TextAlignment::CENTER),
+                ..default()
+            })
+            .insert(Study)
+            .insert(AdviserIcon);
+    }
+}
+
+fn spawn_adviser_icon(
+    parent: &mut ChildBuilder,
+    texture: Handle<Image>,
+    x: f32,
+    y: f32,
+    z: f32,
+    size: f32,
+) {
+    parent
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(size, size)),
+                ..default()
+            },
+            transform: Transform::from_xyz(x, y, z),
+            texture,
+            ..default()
+        })
+        .insert(Study)
+        .insert(AdviserIcon);
 }
 
 pub fn draw_actor_to_pos(
     anim_timer: Res<AnimationTimer>,
-    mut players: Query<
-        (&mut Transform, &mut Position, &NextPosition, &Interact),
-        Or<(With<Robot>, With<Player>)>,
-    >,
+    mut players: Query<(&mut Transform, &mut ActorView), Or<(With<Robot>, With<Player>)>>,
     tile_size: Res<TileSize>,
+    layout: Res<Arc<KitchenLayout>>,
 ) {
     let mut t = anim_timer.0.elapsed().as_millis() as f32 / ANIM_DURATION.as_millis() as f32;
     t = t.clamp(0., 1.);
     t = t * t * (3. - 2. * t);
 
-    let win_size = 2. * TILE_PADDING + NUM_TILES as f32 * tile_size.0;
-    for (mut trans, mut pos, next_pos, interact) in players.iter_mut() {
-        let mut cur_x: f32 =
-            TILE_PADDING + tile_size.0 * pos.x as f32 - win_size * 0.5 + tile_size.0 * 0.5;
-        let mut cur_y: f32 =
-            TILE_PADDING + tile_size.0 * pos.y as f32 - win_size * 0.5 + tile_size.0 * 0.5;
+    for (mut trans, mut view) in players.iter_mut() {
+        let pos = view.pos;
+        let next_pos = view.actor.pos;
+        let cur = tile_center(&layout, tile_size.0, pos.x as f32, pos.y as f32);
+        let (mut cur_x, mut cur_y) = (cur.x, cur.y);
 
-        let mut next_x: f32 =
-            TILE_PADDING + tile_size.0 * next_pos.x as f32 - win_size * 0.5 + tile_size.0 * 0.5;
-        let mut next_y: f32 =
-            TILE_PADDING + tile_size.0 * next_pos.y as f32 - win_size * 0.5 + tile_size.0 * 0.5;
+        let next = tile_center(&layout, tile_size.0, next_pos.x as f32, next_pos.y as f32);
+        let (mut next_x, mut next_y) = (next.x, next.y);
 
         // interact animation offset
-        match *interact {
+        match view.actor.interact {
             Interact::No => (),
             Interact::Stay(interact_pos) => {
                 let dx = interact_pos.x as i32 - pos.x as i32;
@@ -768,8 +618,7 @@ pub fn draw_actor_to_pos(
 
         // if animation is over, we update position
         if anim_timer.0.finished() {
-            pos.x = next_pos.x;
-            pos.y = next_pos.y;
+            view.pos = next_pos;
         }
     }
 }
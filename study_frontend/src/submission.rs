@@ -0,0 +1,276 @@
+use std::sync::{Arc, Mutex};
+#[cfg(target_arch = "wasm32")]
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::{distributions::Alphanumeric, Rng};
+#[cfg(target_arch = "wasm32")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use study_shared_types::{ResultSubmission, StepBatch};
+
+#[cfg(target_arch = "wasm32")]
+const SUBMIT_ATTEMPTS: u32 = 8;
+#[cfg(target_arch = "wasm32")]
+const SUBMIT_BACKOFF: Duration = Duration::from_secs(1);
+#[cfg(target_arch = "wasm32")]
+const SUBMIT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+const IDEMPOTENCY_KEY_LEN: usize = 32;
+/// Local storage key of the results the backend has not confirmed yet.
+#[cfg(target_arch = "wasm32")]
+const PENDING_RESULTS_KEY: &str = "pending_results";
+/// Local storage key of the step batches the backend has not confirmed yet.
+#[cfg(target_arch = "wasm32")]
+const PENDING_STEPS_KEY: &str = "pending_steps";
+
+// only the web build sends anything
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubmissionState {
+    Sending,
+    /// the given attempt failed, waiting before the next one
+    Retrying(u32),
+    Sent,
+    /// the backend refused the result with this status, sending it again would not help
+    Rejected(u16),
+    /// out of attempts, the result is sent again on the next visit
+    Unsent,
+}
+
+/// Shared with the async sender, which cannot insert resources itself.
+pub struct SubmissionStatus(pub Arc<Mutex<SubmissionState>>);
+
+/// A result that the backend has not confirmed yet. The key stays the same for every attempt,
+/// so the backend can tell a retry from a second submission.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingResult {
+    pub idempotency_key: String,
+    pub submission: ResultSubmission,
+}
+
+impl PendingResult {
+    pub fn new(submission: ResultSubmission) -> Self {
+        PendingResult {
+            idempotency_key: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(IDEMPOTENCY_KEY_LEN)
+                .map(char::from)
+                .collect(),
+            submission,
+        }
+    }
+}
+
+/// Starts sending the results and returns their status for the End screen.
+pub fn submit(submission: ResultSubmission) -> SubmissionStatus {
+    let status = Arc::new(Mutex::new(SubmissionState::Sending));
+    let pending = PendingResult::new(submission);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        warn!("Not sending any data in native mode.");
+        info!("Results: {:?}", pending);
+        *status.lock().expect("Submission status poisoned.") = SubmissionState::Unsent;
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        info!("Sending Study Results...");
+
+        // stored first, so that closing the tab right away does not lose them
+        store_pending(&pending);
+        wasm_bindgen_futures::spawn_local(send_with_retry(pending, status.clone()));
+    }
+
+    SubmissionStatus(status)
+}
+
+/// Sends results left over from an earlier visit, e.g. one that ended while offline.
+pub fn resend_pending() {
+    #[cfg(target_arch = "wasm32")]
+    {
+        for pending in load_pending::<PendingResult>(PENDING_RESULTS_KEY) {
+            info!(
+                "Resending the results of participant {}",
+                pending.submission.result.participant_id
+            );
+            let status = Arc::new(Mutex::new(SubmissionState::Sending));
+            wasm_bindgen_futures::spawn_local(send_with_retry(pending, status));
+        }
+        for batch in load_pending::<StepBatch>(PENDING_STEPS_KEY) {
+            wasm_bindgen_futures::spawn_local(send_steps_with_retry(batch));
+        }
+    }
+}
+
+/// Posts a batch of recorded steps in the background. Like results, the batch is kept until the
+/// backend confirms it; the backend skips steps it already stored, so resending is harmless.
+pub fn send_steps(batch: StepBatch) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        info!("Not sending {} steps in native mode.", batch.events.len());
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        update_pending(PENDING_STEPS_KEY, |all: &mut Vec<StepBatch>| {
+            all.push(batch.clone())
+        });
+        wasm_bindgen_futures::spawn_local(send_steps_with_retry(batch));
+    }
+}
+
+/// How a request ended after retrying.
+#[cfg(target_arch = "wasm32")]
+enum Delivery {
+    Accepted,
+    /// refused with a status that sending again would not change
+    Rejected(u16),
+    GaveUp,
+}
+
+/// Posts `body` until the backend accepts or refuses it, backing off in between. `on_retry` is
+/// called with the failed attempt before waiting.
+#[cfg(target_arch = "wasm32")]
+async fn post_with_retry(
+    what: &str,
+    path: &str,
+    body: String,
+    headers: &[(&str, &str)],
+    mut on_retry: impl FnMut(u32),
+) -> Delivery {
+    let mut delay = SUBMIT_BACKOFF;
+    for attempt in 1..=SUBMIT_ATTEMPTS {
+        match crate::backend::fetch_with_headers("POST", path, Some(body.clone()), headers).await {
+            Ok((200..=299, _)) => return Delivery::Accepted,
+            // timeouts, rate limits and server errors may go away on their own
+            Ok((code @ (408 | 429 | 500..=599), _)) => {
+                warn!("{} not accepted with status {}, retrying.", what, code)
+            }
+            Ok((code, reason)) => {
+                error!("{} rejected with status {}: {}", what, code, reason);
+                return Delivery::Rejected(code);
+            }
+            Err(e) => warn!("Could not send {}: {:?}", what.to_lowercase(), e),
+        }
+
+        if attempt < SUBMIT_ATTEMPTS {
+            on_retry(attempt);
+            crate::backend::sleep(delay).await;
+            delay = (delay * 2).min(SUBMIT_MAX_BACKOFF);
+        }
+    }
+
+    error!("Giving up on sending {} for now.", what.to_lowercase());
+    Delivery::GaveUp
+}
+
+/// Batches are told apart by their participant and first step.
+#[cfg(target_arch = "wasm32")]
+fn same_batch(a: &StepBatch, b: &StepBatch) -> bool {
+    a.participant_id == b.participant_id
+        && a.events.first().map(|e| e.step) == b.events.first().map(|e| e.step)
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn send_steps_with_retry(batch: StepBatch) {
+    let body = match serde_json::to_string(&batch) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not encode steps: {}", e);
+            return;
+        }
+    };
+
+    match post_with_retry("Steps", "steps", body, &[], |_| ()).await {
+        // kept for the next visit
+        Delivery::GaveUp => (),
+        Delivery::Accepted | Delivery::Rejected(_) => {
+            update_pending(PENDING_STEPS_KEY, |all: &mut Vec<StepBatch>| {
+                all.retain(|b| !same_batch(b, &batch))
+            })
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn send_with_retry(pending: PendingResult, status: Arc<Mutex<SubmissionState>>) {
+    let set_status = |state| *status.lock().expect("Submission status poisoned.") = state;
+
+    let body = match serde_json::to_string(&pending.submission) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not encode results: {}", e);
+            set_status(SubmissionState::Unsent);
+            return;
+        }
+    };
+    let headers = [("idempotency-key", pending.idempotency_key.as_str())];
+
+    let retrying = |attempt| set_status(SubmissionState::Retrying(attempt));
+    match post_with_retry("Results", "data", body, &headers, retrying).await {
+        Delivery::Accepted => {
+            info!("Results submitted.");
+            remove_pending(&pending.idempotency_key);
+            set_status(SubmissionState::Sent);
+        }
+        Delivery::Rejected(code) => {
+            remove_pending(&pending.idempotency_key);
+            set_status(SubmissionState::Rejected(code));
+        }
+        Delivery::GaveUp => set_status(SubmissionState::Unsent),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_pending<T: DeserializeOwned>(key: &str) -> Vec<T> {
+    local_storage()
+        .and_then(|s| s.get_item(key).ok()?)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_pending<T: Serialize>(key: &str, pending: &[T]) {
+    let storage = match local_storage() {
+        Some(s) => s,
+        None => {
+            warn!("No local storage, unsent data is lost on reload.");
+            return;
+        }
+    };
+    match serde_json::to_string(pending) {
+        Ok(json) => {
+            if let Err(e) = storage.set_item(key, &json) {
+                warn!("Could not store unsent data: {:?}", e);
+            }
+        }
+        Err(e) => error!("Could not encode unsent data: {}", e),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn update_pending<T: Serialize + DeserializeOwned>(key: &str, update: impl FnOnce(&mut Vec<T>)) {
+    let mut all = load_pending(key);
+    update(&mut all);
+    save_pending(key, &all);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn store_pending(pending: &PendingResult) {
+    update_pending(PENDING_RESULTS_KEY, |all: &mut Vec<PendingResult>| {
+        all.retain(|p| p.idempotency_key != pending.idempotency_key);
+        all.push(pending.clone());
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn remove_pending(idempotency_key: &str) {
+    update_pending(PENDING_RESULTS_KEY, |all: &mut Vec<PendingResult>| {
+        all.retain(|p| p.idempotency_key != idempotency_key)
+    });
+}
@@ -0,0 +1,154 @@
+use crate::{
+    kitchen::{next_pos_from_move, obs_from_pos, valid_moves, Actor, Interact, Position},
+    layout::{Agent, KitchenLayout, Station, StationKind},
+    synth::Guards,
+    NextMove,
+};
+
+/// The advice shown to the human while they choose their next move. Each adviser holds if any
+/// of its guards does.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ActiveAdvisers {
+    /// observations the human must not make
+    pub safety: Vec<Guards>,
+    /// observations the human should make, one of each adviser
+    pub fairness: Vec<Guards>,
+    pub next_move: NextMove,
+}
+
+impl ActiveAdvisers {
+    pub fn clear_all(&mut self) {
+        self.safety.clear();
+        self.fairness.clear();
+    }
+
+    pub fn safety_violated(&self, obs: &str) -> bool {
+        self.safety.iter().any(|guards| obs_match_any(obs, guards))
+    }
+
+    /// Whether the observation does what one of the fairness advisers asks for.
+    pub fn fairness_met(&self, obs: &str) -> bool {
+        self.fairness
+            .iter()
+            .any(|guards| obs_match_any(obs, guards))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.safety.is_empty() && self.fairness.is_empty()
+    }
+}
+
+/// Guards have one character per atomic proposition: `1` if it holds, `0` if it does not and
+/// `X` for either.
+pub fn obs_match_guard(obs: &str, guard: &str) -> bool {
+    assert_eq!(obs.len(), guard.len());
+    for (i, c) in guard.chars().enumerate() {
+        if c == 'X' {
+            continue;
+        }
+        if c != obs
+            .chars()
+            .nth(i)
+            .expect("obs_match_guard: obs and guard should be same length!")
+        {
+            return false;
+        }
+    }
+    true
+}
+
+/// Whether the observation matches one of the guards.
+pub fn obs_match_any(obs: &str, guards: &[String]) -> bool {
+    guards.iter().any(|guard| obs_match_guard(obs, guard))
+}
+
+/// The literals of a guard as the index of the atomic proposition and whether it has to hold.
+pub fn guard_literals(guard: &str) -> impl Iterator<Item = (usize, bool)> + '_ {
+    guard.chars().enumerate().filter_map(|(i, c)| match c {
+        '1' => Some((i, true)),
+        '0' => Some((i, false)),
+        _ => None,
+    })
+}
+
+/// The advice of the strict adviser condition: the first move of a shortest walk to the
+/// station the human should use next, if the safety advisers allow what the human observes.
+///
+/// The human helps where the fairness advisers ask for it, and otherwise takes the next
+/// ingredient their recipe asks for or delivers. The whole walk is checked against the safety
+/// advisers, every tile on the way and the interaction at its end, so stations the human must
+/// not use are passed by for the next allowed one. The advice is worked out again in every step
+/// with the advisers of that step. If the advisers forbid every station, the human walks to the
+/// nearest one and idles there.
+pub fn next_move_advice(
+    layout: &KitchenLayout,
+    human: &Actor,
+    advisers: &ActiveAdvisers,
+    guard_ap: &[String],
+) -> NextMove {
+    let valid = valid_moves(layout, Agent::Human, human.pos, &human.interact);
+    if human.interact.is_active() {
+        return valid[0];
+    }
+
+    let interact_obs = |station: &Station| {
+        obs_from_pos(
+            layout,
+            station.pos,
+            &Interact::In(station.counter),
+            guard_ap,
+        )
+    };
+    let walkable = |pos: Position| {
+        !advisers.safety_violated(&obs_from_pos(layout, pos, &Interact::No, guard_ap))
+    };
+    let usable = |pos: Position| {
+        walkable(pos)
+            && layout
+                .station_at(Agent::Human, pos)
+                .is_some_and(|station| !advisers.safety_violated(&interact_obs(station)))
+    };
+    let safe = |next_move: NextMove| match next_move {
+        NextMove::Interact => usable(human.pos),
+        _ => walkable(next_pos_from_move(&human.pos, next_move)),
+    };
+
+    let stations: Vec<&Station> = layout.stations_of(Agent::Human).collect();
+    let mut targets: Vec<Position> = stations
+        .iter()
+        .filter(|s| advisers.fairness_met(&interact_obs(s)))
+        .map(|s| s.pos)
+        .collect();
+    if targets.is_empty() {
+        let recipe = layout.recipe(Agent::Human);
+        let ready = human.progress.ready(recipe);
+        targets = stations
+            .iter()
+            .filter(|s| match &s.kind {
+                StationKind::Delivery => ready,
+                StationKind::Ingredient(i) => !ready && human.progress.can_take(recipe, i),
+            })
+            .map(|s| s.pos)
+            .collect();
+    }
+    if targets.iter().any(|&pos| usable(pos)) {
+        targets.retain(|&pos| usable(pos));
+    }
+
+    if targets.contains(&human.pos) {
+        return if safe(NextMove::Interact) {
+            NextMove::Interact
+        } else {
+            NextMove::Idle
+        };
+    }
+    let distances = layout.floor_distances_where(&targets, walkable);
+    valid
+        .into_iter()
+        .filter(|&m| m != NextMove::Interact && safe(m))
+        .min_by_key(|&m| {
+            let pos = next_pos_from_move(&human.pos, m);
+            distances.get(&pos).copied().unwrap_or(usize::MAX)
+        })
+        .unwrap_or(NextMove::Idle)
+}
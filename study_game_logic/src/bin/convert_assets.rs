@@ -0,0 +1,57 @@
+//! Converts the JSON written by the synthesis tool into the binary assets the frontend loads:
+//!
+//! ```sh
+//! cargo run --bin convert_assets -- ../study_frontend/assets/data/game.json.game ../study_frontend/assets/data/game.bin.game
+//! ```
+//!
+//! Games end in `.game` and strategies in `.strat`.
+
+use std::{error::Error, fs, process};
+
+use study_game_logic::{Strategy, SynthGame};
+
+fn convert(input: &str, output: &str) -> Result<(usize, usize), Box<dyn Error>> {
+    let json = fs::read_to_string(input).map_err(|e| format!("Could not read {}: {}", input, e))?;
+    let bytes = if input.ends_with(".game") {
+        let game = SynthGame::from_json(&json)?;
+        let bytes = game.to_binary();
+        // never write a file that would load differently
+        if SynthGame::from_binary(&bytes)? != game {
+            return Err("the binary game does not load to the same graph".into());
+        }
+        bytes
+    } else if input.ends_with(".strat") {
+        let strategy = Strategy::from_json(&json)?;
+        let bytes = strategy.to_binary();
+        if Strategy::from_binary(&bytes)? != strategy {
+            return Err("the binary strategy does not load to the same strategy".into());
+        }
+        bytes
+    } else {
+        return Err(format!("{} is neither a .game nor a .strat file", input).into());
+    };
+
+    fs::write(output, &bytes).map_err(|e| format!("Could not write {}: {}", output, e))?;
+    Ok((json.len(), bytes.len()))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (input, output) = match args.as_slice() {
+        [input, output] => (input, output),
+        _ => {
+            eprintln!("usage: convert_assets <input.json.game|input.json.strat> <output>");
+            process::exit(2);
+        }
+    };
+
+    match convert(input, output) {
+        Ok((json_len, binary_len)) => {
+            println!("{}: {} -> {} bytes", output, json_len, binary_len)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
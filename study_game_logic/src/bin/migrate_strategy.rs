@@ -0,0 +1,136 @@
+//! Converts a strategy keyed by Python tuple strings, e.g. `"('20', '1')": "up"`, into the
+//! current format keyed by game states:
+//!
+//! ```sh
+//! cargo run --bin migrate_strategy -- ../study_frontend/assets/data/game.json.game old.json.strat ../study_frontend/assets/data/strat.json.strat
+//! ```
+//!
+//! The old keys only name the robot and task part of a state, the game is needed to find the
+//! robot states they stand for.
+
+use std::{collections::HashMap, error::Error, fs, process};
+
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Map, Serializer, Value};
+use study_game_logic::{GraphState, Strategy, SynthGame};
+
+/// `NodeData::player` of robot states
+const ROBOT_PLAYER: u8 = 1;
+
+/// In the field order and indentation the synthesis tool writes.
+#[derive(Serialize)]
+struct Migrated<'a> {
+    strat: Vec<(GraphState, String)>,
+    safety_adv: &'a Value,
+    fairness_adv: &'a Value,
+    guard_ap: &'a Value,
+}
+
+/// Splits `"('20', '1')"` into `("20", "1")`.
+fn parse_key(key: &str) -> Option<(String, String)> {
+    let inner = key.trim().strip_prefix('(')?.strip_suffix(')')?;
+    let mut parts = inner.split(',').map(|part| {
+        let part = part.trim();
+        part.strip_prefix('\'')
+            .and_then(|p| p.strip_suffix('\''))
+            .or_else(|| part.strip_prefix('"').and_then(|p| p.strip_suffix('"')))
+    });
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Some(robot)), Some(Some(task)), None) => Some((robot.to_owned(), task.to_owned())),
+        _ => None,
+    }
+}
+
+fn migrate(
+    game: &SynthGame,
+    old: &Map<String, Value>,
+) -> Result<Vec<(GraphState, String)>, String> {
+    let mut robot_states: HashMap<(&str, &str), Vec<&GraphState>> = HashMap::new();
+    for node in game.nodes.iter().filter(|n| n.player == ROBOT_PLAYER) {
+        robot_states
+            .entry((node.id.0.as_str(), node.id.1.as_str()))
+            .or_default()
+            .push(&node.id);
+    }
+
+    let mut strat = Vec::new();
+    let mut problems = Vec::new();
+    for (key, next_move) in old {
+        let next_move = match next_move.as_str() {
+            Some(next_move) => next_move,
+            None => {
+                problems.push(format!("{}: the move is not a string", key));
+                continue;
+            }
+        };
+        let (robot, task) = match parse_key(key) {
+            Some(parts) => parts,
+            None => {
+                problems.push(format!("{}: not a tuple of two strings", key));
+                continue;
+            }
+        };
+        match robot_states.get(&(robot.as_str(), task.as_str())) {
+            Some(states) => strat.extend(states.iter().map(|&s| (s.clone(), next_move.to_owned()))),
+            None => problems.push(format!("{}: the game has no such robot state", key)),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems.join("\n"));
+    }
+    strat.sort();
+    Ok(strat)
+}
+
+fn run(game: &str, input: &str, output: &str) -> Result<usize, Box<dyn Error>> {
+    let game_json =
+        fs::read_to_string(game).map_err(|e| format!("Could not read {}: {}", game, e))?;
+    let game = SynthGame::from_json(&game_json)?;
+    let input_json =
+        fs::read_to_string(input).map_err(|e| format!("Could not read {}: {}", input, e))?;
+    let strategy: Value = serde_json::from_str(&input_json)?;
+
+    let old = match strategy["strat"].as_object() {
+        Some(old) => old,
+        None => return Err(format!("{} has no tuple string keys to migrate", input).into()),
+    };
+    let strat = migrate(&game, old)?;
+    let entries = strat.len();
+    let migrated = Migrated {
+        strat,
+        safety_adv: &strategy["safety_adv"],
+        fairness_adv: &strategy["fairness_adv"],
+        guard_ap: &strategy["guard_ap"],
+    };
+
+    let mut bytes = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut bytes, PrettyFormatter::with_indent(b"    "));
+    migrated.serialize(&mut serializer)?;
+    let migrated = String::from_utf8(bytes)?;
+
+    // never write a file that does not load
+    Strategy::from_json(&migrated)?;
+    fs::write(output, migrated).map_err(|e| format!("Could not write {}: {}", output, e))?;
+    Ok(entries)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (game, input, output) = match args.as_slice() {
+        [game, input, output] => (game, input, output),
+        _ => {
+            eprintln!("usage: migrate_strategy <game.json.game> <old.json.strat> <output>");
+            process::exit(2);
+        }
+    };
+
+    match run(game, input, output) {
+        Ok(entries) => println!("{}: {} strategy entries", output, entries),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
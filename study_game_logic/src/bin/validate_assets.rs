@@ -0,0 +1,70 @@
+//! Checks that a game, a strategy and the kitchen layout fit together before they are deployed:
+//!
+//! ```sh
+//! cargo run --bin validate_assets -- ../study_frontend/assets/data/game.bin.game ../study_frontend/assets/data/strat.bin.strat ../study_frontend/assets/data/tiles.json.tiles
+//! ```
+//!
+//! Game and strategy are read as JSON unless the file name contains `.bin.`.
+
+use std::{error::Error, fs, process};
+
+use study_game_logic::{validate, KitchenLayout, Strategy, SynthGame};
+
+fn load_game(path: &str) -> Result<SynthGame, Box<dyn Error>> {
+    if path.contains(".bin.") {
+        let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Ok(SynthGame::from_binary(&bytes)?)
+    } else {
+        let json =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Ok(SynthGame::from_json(&json)?)
+    }
+}
+
+fn load_strategy(path: &str) -> Result<Strategy, Box<dyn Error>> {
+    if path.contains(".bin.") {
+        let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Ok(Strategy::from_binary(&bytes)?)
+    } else {
+        let json =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Ok(Strategy::from_json(&json)?)
+    }
+}
+
+fn load_layout(path: &str) -> Result<KitchenLayout, Box<dyn Error>> {
+    let json = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    Ok(KitchenLayout::from_json(&json)?)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (game, strategy, layout) = match args.as_slice() {
+        [game, strategy, layout] => (game, strategy, layout),
+        _ => {
+            eprintln!("usage: validate_assets <game> <strategy> <layout>");
+            process::exit(2);
+        }
+    };
+
+    let loaded =
+        load_game(game).and_then(|game| Ok((game, load_strategy(strategy)?, load_layout(layout)?)));
+    let (game, strategy, layout) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let errors = validate(&game, &strategy, &layout);
+    if errors.is_empty() {
+        println!("ok");
+        return;
+    }
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    eprintln!("{} problems found", errors.len());
+    process::exit(1);
+}
@@ -0,0 +1,334 @@
+//! A compact binary encoding of [`SynthGame`] and [`Strategy`]. The JSON written by the
+//! synthesis tool repeats every state as a tuple of strings, here each string is stored once
+//! and states, nodes and edges refer to it by index. JSON stays the authoring format, the
+//! `convert_assets` tool writes the binary files the frontend loads.
+
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::synth::{
+    Graph, GraphState, Guards, LinkData, NodeData, Strategy, StrategyData, SynthGame, SynthGameData,
+};
+
+const GAME_MAGIC: &[u8; 4] = b"SYNG";
+const STRATEGY_MAGIC: &[u8; 4] = b"SYNS";
+/// Bumped whenever the layout of the encoded structs changes.
+pub const BINARY_VERSION: u8 = 2;
+
+#[derive(Debug)]
+pub enum BinaryError {
+    /// the bytes are not an encoded game or strategy
+    WrongMagic,
+    UnsupportedVersion(u8),
+    Malformed(String),
+}
+
+impl Display for BinaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryError::WrongMagic => write!(f, "not a binary game asset"),
+            BinaryError::UnsupportedVersion(v) => {
+                write!(f, "unsupported binary asset version {}", v)
+            }
+            BinaryError::Malformed(e) => write!(f, "malformed binary asset: {}", e),
+        }
+    }
+}
+
+impl Error for BinaryError {}
+
+impl From<bincode::Error> for BinaryError {
+    fn from(e: bincode::Error) -> Self {
+        BinaryError::Malformed(e.to_string())
+    }
+}
+
+/// Each distinct string and state gets an index, in order of first use.
+#[derive(Default)]
+struct Interner {
+    strings: Vec<String>,
+    string_ids: HashMap<String, u32>,
+    states: Vec<[u32; 3]>,
+    state_ids: HashMap<GraphState, u32>,
+}
+
+impl Interner {
+    fn string(&mut self, s: &str) -> u32 {
+        if let Some(&id) = self.string_ids.get(s) {
+            return id;
+        }
+        let id = self.strings.len() as u32;
+        self.strings.push(s.to_owned());
+        self.string_ids.insert(s.to_owned(), id);
+        id
+    }
+
+    fn state(&mut self, state: &GraphState) -> u32 {
+        if let Some(&id) = self.state_ids.get(state) {
+            return id;
+        }
+        let parts = [
+            self.string(&state.0),
+            self.string(&state.1),
+            self.string(&state.2),
+        ];
+        let id = self.states.len() as u32;
+        self.states.push(parts);
+        self.state_ids.insert(state.clone(), id);
+        id
+    }
+
+    fn strings(&mut self, strings: &[String]) -> Vec<u32> {
+        strings.iter().map(|s| self.string(s)).collect()
+    }
+}
+
+/// Resolves the indices of an [`Interner`] again, with bounds checks since the input is untrusted.
+struct Table {
+    strings: Vec<String>,
+    states: Vec<GraphState>,
+}
+
+impl Table {
+    fn new(strings: Vec<String>, states: &[[u32; 3]]) -> Result<Self, BinaryError> {
+        let mut table = Table {
+            strings,
+            states: Vec::with_capacity(states.len()),
+        };
+        for [a, b, c] in states {
+            let state = (table.string(*a)?, table.string(*b)?, table.string(*c)?);
+            table.states.push(state);
+        }
+        Ok(table)
+    }
+
+    fn string(&self, id: u32) -> Result<String, BinaryError> {
+        self.strings
+            .get(id as usize)
+            .cloned()
+            .ok_or_else(|| BinaryError::Malformed(format!("unknown string {}", id)))
+    }
+
+    fn state(&self, id: u32) -> Result<GraphState, BinaryError> {
+        self.states
+            .get(id as usize)
+            .cloned()
+            .ok_or_else(|| BinaryError::Malformed(format!("unknown state {}", id)))
+    }
+
+    fn strings(&self, ids: &[u32]) -> Result<Vec<String>, BinaryError> {
+        ids.iter().map(|&id| self.string(id)).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompactGame {
+    strings: Vec<String>,
+    states: Vec<[u32; 3]>,
+    directed: bool,
+    multigraph: bool,
+    acc: Vec<u32>,
+    init: u32,
+    human_ap: Vec<u32>,
+    mdp_ap: Vec<u32>,
+    nodes: Vec<CompactNode>,
+    links: Vec<CompactLink>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompactNode {
+    id: u32,
+    player: u8,
+    ap: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompactLink {
+    source: u32,
+    target: u32,
+    act: Option<u32>,
+    guards: Option<Vec<u32>>,
+    prob: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompactStrategy {
+    strings: Vec<String>,
+    states: Vec<[u32; 3]>,
+    strat: Vec<(u32, u32)>,
+    safety_adv: Vec<(u32, Vec<u32>)>,
+    fairness_adv: Vec<(u32, Vec<u32>)>,
+    guard_ap: Vec<u32>,
+}
+
+fn write_header(magic: &[u8; 4]) -> Vec<u8> {
+    let mut bytes = magic.to_vec();
+    bytes.push(BINARY_VERSION);
+    bytes
+}
+
+fn read_header<'a>(bytes: &'a [u8], magic: &[u8; 4]) -> Result<&'a [u8], BinaryError> {
+    match bytes {
+        [m0, m1, m2, m3, version, rest @ ..] if [*m0, *m1, *m2, *m3] == *magic => {
+            if *version != BINARY_VERSION {
+                return Err(BinaryError::UnsupportedVersion(*version));
+            }
+            Ok(rest)
+        }
+        _ => Err(BinaryError::WrongMagic),
+    }
+}
+
+impl SynthGame {
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut interner = Interner::default();
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|n| CompactNode {
+                id: interner.state(&n.id),
+                player: n.player,
+                ap: n.ap.as_deref().map(|ap| interner.string(ap)),
+            })
+            .collect();
+        let links = self
+            .links
+            .iter()
+            .map(|l| CompactLink {
+                source: interner.state(&l.source),
+                target: interner.state(&l.target),
+                act: l.act.as_deref().map(|act| interner.string(act)),
+                guards: l.guards.as_deref().map(|g| interner.strings(g)),
+                prob: l.prob,
+            })
+            .collect();
+        let acc = self.graph.acc.iter().map(|s| interner.state(s)).collect();
+        let init = interner.state(&self.graph.init);
+        let human_ap = interner.strings(&self.graph.human_ap);
+        let mdp_ap = interner.strings(&self.graph.mdp_ap);
+
+        let compact = CompactGame {
+            strings: interner.strings,
+            states: interner.states,
+            directed: self.directed,
+            multigraph: self.multigraph,
+            acc,
+            init,
+            human_ap,
+            mdp_ap,
+            nodes,
+            links,
+        };
+        let mut bytes = write_header(GAME_MAGIC);
+        bincode::serialize_into(&mut bytes, &compact).expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let compact: CompactGame = bincode::deserialize(read_header(bytes, GAME_MAGIC)?)?;
+        let table = Table::new(compact.strings, &compact.states)?;
+
+        let nodes = compact
+            .nodes
+            .iter()
+            .map(|n| {
+                Ok(NodeData {
+                    player: n.player,
+                    ap: n.ap.map(|ap| table.string(ap)).transpose()?,
+                    id: table.state(n.id)?,
+                })
+            })
+            .collect::<Result<_, BinaryError>>()?;
+        let links = compact
+            .links
+            .iter()
+            .map(|l| {
+                Ok(LinkData {
+                    act: l.act.map(|act| table.string(act)).transpose()?,
+                    guards: l.guards.as_deref().map(|g| table.strings(g)).transpose()?,
+                    prob: l.prob,
+                    source: table.state(l.source)?,
+                    target: table.state(l.target)?,
+                })
+            })
+            .collect::<Result<_, BinaryError>>()?;
+        let graph = Graph {
+            acc: compact
+                .acc
+                .iter()
+                .map(|&s| table.state(s))
+                .collect::<Result<_, _>>()?,
+            init: table.state(compact.init)?,
+            human_ap: table.strings(&compact.human_ap)?,
+            mdp_ap: table.strings(&compact.mdp_ap)?,
+        };
+
+        Ok(SynthGameData {
+            directed: compact.directed,
+            multigraph: compact.multigraph,
+            graph,
+            nodes,
+            links,
+        }
+        .into())
+    }
+}
+
+impl Strategy {
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut interner = Interner::default();
+
+        let strat: Vec<(u32, u32)> = self
+            .strat
+            .iter()
+            .map(|(state, next_move)| (interner.state(state), interner.string(next_move)))
+            .collect();
+        let mut advisers = |advisers: &[(GraphState, Guards)]| -> Vec<(u32, Vec<u32>)> {
+            advisers
+                .iter()
+                .map(|(state, guards)| (interner.state(state), interner.strings(guards)))
+                .collect()
+        };
+        let safety_adv = advisers(&self.safety_adv);
+        let fairness_adv = advisers(&self.fairness_adv);
+        let guard_ap = interner.strings(&self.guard_ap);
+
+        let compact = CompactStrategy {
+            strings: interner.strings,
+            states: interner.states,
+            strat,
+            safety_adv,
+            fairness_adv,
+            guard_ap,
+        };
+        let mut bytes = write_header(STRATEGY_MAGIC);
+        bincode::serialize_into(&mut bytes, &compact).expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let compact: CompactStrategy = bincode::deserialize(read_header(bytes, STRATEGY_MAGIC)?)?;
+        let table = Table::new(compact.strings, &compact.states)?;
+
+        let strat = compact
+            .strat
+            .iter()
+            .map(|&(state, next_move)| Ok((table.state(state)?, table.string(next_move)?)))
+            .collect::<Result<_, BinaryError>>()?;
+        let advisers = |advisers: &[(u32, Vec<u32>)]| {
+            advisers
+                .iter()
+                .map(|(state, guards)| Ok((table.state(*state)?, table.strings(guards)?)))
+                .collect::<Result<Vec<_>, BinaryError>>()
+        };
+
+        Ok(StrategyData {
+            strat,
+            safety_adv: advisers(&compact.safety_adv)?,
+            fairness_adv: advisers(&compact.fairness_adv)?,
+            guard_ap: table.strings(&compact.guard_ap)?,
+        }
+        .into())
+    }
+}
@@ -0,0 +1,321 @@
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, SeedableRng};
+use study_shared_types::AdviserMode;
+
+use crate::{
+    advisers::{next_move_advice, ActiveAdvisers},
+    kitchen::{self, obs_from_pos, Actor, Interact},
+    layout::{Agent, KitchenLayout},
+    synth::{move_between, GraphState, RobotLocations, Strategy, StrategyError, SynthGame},
+    NextMove,
+};
+
+/// Everything that happened in one step. Actors and game state are the ones right after the
+/// moves, so they can be shown even if the step violated an adviser and the game was reset.
+#[derive(Debug, Clone)]
+pub struct StepOutcome {
+    pub human_move: NextMove,
+    /// the move the robot made, which differs from the chosen one if it failed
+    pub robot_move: NextMove,
+    pub human: Actor,
+    pub robot: Actor,
+    pub observation: String,
+    pub game_state: GraphState,
+    /// the advice the human saw while choosing their move
+    pub advisers: ActiveAdvisers,
+    pub human_burger: bool,
+    pub robot_burger: bool,
+    /// the human ignored the advice, the game is back at the start
+    pub violated: bool,
+    /// set if the step ended in a probabilistic state with more than one outcome
+    pub sampled: Option<SampledTransition>,
+}
+
+/// The outcome drawn for a probabilistic state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledTransition {
+    pub prob_state: GraphState,
+    pub outcome: GraphState,
+    pub prob: f32,
+    /// the move the strategy chose for the robot
+    pub robot_move: NextMove,
+}
+
+/// The cooperative kitchen game. The robot plays the synthesized strategy and the human is
+/// advised according to the adviser mode. Every step takes one human move, the robot's move
+/// for it is chosen in advance. Probabilistic outcomes are drawn from an RNG seeded with
+/// `seed`, so a session can be replayed.
+pub struct KitchenGame {
+    synth_game: Arc<SynthGame>,
+    strategy: Arc<Strategy>,
+    layout: Arc<KitchenLayout>,
+    robot_locations: RobotLocations,
+    adviser_mode: AdviserMode,
+    state: GraphState,
+    human: Actor,
+    robot: Actor,
+    robot_move: NextMove,
+    advisers: ActiveAdvisers,
+    rng: StdRng,
+    /// why the robot's next move is not the strategy's
+    strategy_error: Option<StrategyError>,
+}
+
+impl KitchenGame {
+    pub fn new(
+        synth_game: Arc<SynthGame>,
+        strategy: Arc<Strategy>,
+        layout: Arc<KitchenLayout>,
+        adviser_mode: AdviserMode,
+        seed: u64,
+    ) -> Self {
+        let mut game = KitchenGame {
+            state: synth_game.graph.init.clone(),
+            robot_locations: RobotLocations::new(&synth_game, &layout),
+            synth_game,
+            strategy,
+            human: Actor::new(layout.start(Agent::Human)),
+            robot: Actor::new(layout.start(Agent::Robot)),
+            layout,
+            adviser_mode,
+            robot_move: NextMove::Idle,
+            advisers: ActiveAdvisers::default(),
+            rng: StdRng::seed_from_u64(seed),
+            strategy_error: None,
+        };
+        game.prepare_robot_move();
+        game
+    }
+
+    pub fn synth_game(&self) -> &SynthGame {
+        &self.synth_game
+    }
+
+    pub fn layout(&self) -> &KitchenLayout {
+        &self.layout
+    }
+
+    /// Where the robot is in each state of the game.
+    pub fn robot_locations(&self) -> &RobotLocations {
+        &self.robot_locations
+    }
+
+    pub fn adviser_mode(&self) -> AdviserMode {
+        self.adviser_mode
+    }
+
+    pub fn state(&self) -> &GraphState {
+        &self.state
+    }
+
+    pub fn human(&self) -> &Actor {
+        &self.human
+    }
+
+    pub fn robot(&self) -> &Actor {
+        &self.robot
+    }
+
+    /// The move the robot makes in the next step.
+    pub fn robot_move(&self) -> NextMove {
+        self.robot_move
+    }
+
+    pub fn advisers(&self) -> &ActiveAdvisers {
+        &self.advisers
+    }
+
+    /// Set if the strategy had no move for the robot's current state. The robot then makes the
+    /// first possible move instead, which the validation of the strategy should rule out.
+    pub fn strategy_error(&self) -> Option<&StrategyError> {
+        self.strategy_error.as_ref()
+    }
+
+    pub fn valid_human_moves(&self) -> Vec<NextMove> {
+        kitchen::valid_moves(
+            &self.layout,
+            Agent::Human,
+            self.human.pos,
+            &self.human.interact,
+        )
+    }
+
+    /// The move the human means with a key, see `kitchen::contextual_move`.
+    pub fn input_move(&self, pressed: NextMove) -> NextMove {
+        kitchen::contextual_move(
+            &self.layout,
+            Agent::Human,
+            self.human.pos,
+            &self.human.interact,
+            pressed,
+        )
+    }
+
+    /// Plays one step. Invalid human moves are replaced by the first valid one.
+    pub fn step(&mut self, human_move: NextMove) -> StepOutcome {
+        let valid_moves = self.valid_human_moves();
+        let human_move = if valid_moves.contains(&human_move) {
+            human_move
+        } else {
+            valid_moves[0]
+        };
+        let chosen_robot_move = self.robot_move;
+
+        // the human's observation does not depend on the robot, so the graph can be stepped
+        // before it is known whether the robot's move succeeds
+        let (mut human, mut robot) = (self.human.clone(), self.robot.clone());
+        kitchen::resolve_moves(
+            &self.layout,
+            &mut human,
+            &mut robot,
+            human_move,
+            chosen_robot_move,
+        );
+        let obs = obs_from_pos(
+            &self.layout,
+            human.pos,
+            &human.interact,
+            &self.synth_game.graph.human_ap,
+        );
+
+        // update synthesis game state
+        let prob_state: GraphState = self.synth_game.apply_human_obs(&self.state, &obs);
+        let outcomes = self.synth_game.edges_from(&prob_state).count();
+        let edge = self.synth_game.skip_prob_state(&prob_state, &mut self.rng);
+        self.state = edge.target.clone();
+
+        let mut sampled = None;
+        let mut robot_move = chosen_robot_move;
+        if outcomes > 1 {
+            // the robot is drawn where the outcome put it, e.g. still in front of the counter
+            // after a failed grasp
+            let from = (self.robot.pos, self.robot.interact.is_active());
+            robot_move = self
+                .robot_locations
+                .get(&edge.target)
+                .and_then(|to| move_between(from, to))
+                .unwrap_or(chosen_robot_move);
+            sampled = Some(SampledTransition {
+                prob_state,
+                outcome: self.state.clone(),
+                prob: edge.prob.unwrap_or(0.0),
+                robot_move: chosen_robot_move,
+            });
+        }
+
+        let (human_burger, robot_burger) = kitchen::resolve_moves(
+            &self.layout,
+            &mut self.human,
+            &mut self.robot,
+            human_move,
+            robot_move,
+        );
+
+        // check for adviser violation
+        let violated = match self.adviser_mode {
+            AdviserMode::LeastLimiting => self.advisers.safety_violated(&obs),
+            AdviserMode::None => self.advisers.safety_violated(&obs),
+            AdviserMode::NextMove => human_move != self.advisers.next_move,
+        };
+
+        let outcome = StepOutcome {
+            human_move,
+            robot_move,
+            human: self.human.clone(),
+            robot: self.robot.clone(),
+            observation: obs,
+            game_state: self.state.clone(),
+            advisers: self.advisers.clone(),
+            human_burger,
+            robot_burger,
+            violated,
+            sampled,
+        };
+
+        if violated {
+            self.reset();
+        } else {
+            self.prepare_robot_move();
+        }
+        outcome
+    }
+
+    /// Puts both actors back to the start and drops their ingredients.
+    fn reset(&mut self) {
+        for (actor, start) in [
+            (&mut self.human, self.layout.start(Agent::Human)),
+            (&mut self.robot, self.layout.start(Agent::Robot)),
+        ] {
+            actor.pos = start;
+            actor.interact = Interact::No;
+            actor.progress.reset();
+        }
+        self.state = self.synth_game.graph.init.clone();
+        self.prepare_robot_move();
+    }
+
+    /// Chooses the robot's next move and the advice for the human state it leads to.
+    fn prepare_robot_move(&mut self) {
+        self.strategy_error = None;
+        if self.synth_game.is_accepting(&self.state)
+            && kitchen::is_delivering(&self.layout, Agent::Robot, &self.robot)
+        {
+            // the burger is delivered, the task starts over where the robot is
+            self.state = self.synth_game.restart_state(&self.state);
+            if self.state == self.synth_game.graph.init {
+                // a game without restart states starts over from the start, so does the robot
+                self.robot.pos = self.layout.start(Agent::Robot);
+                self.robot.interact = Interact::No;
+            }
+        }
+        let robot = &self.robot;
+
+        // once the task is done, the robot delivers before following the strategy again
+        let delivery = if self.synth_game.is_accepting(&self.state) {
+            kitchen::delivery_move(
+                &self.layout,
+                Agent::Robot,
+                robot.pos,
+                robot.interact.is_active(),
+                &self.synth_game.valid_robot_moves(&self.state),
+            )
+        } else {
+            None
+        };
+        let robot_move = match delivery {
+            Some(next_move) => next_move,
+            None => match self.strategy.next_move(&self.state) {
+                Ok(next_move) => next_move,
+                Err(e) => {
+                    // keep the session going, but let the caller know
+                    self.strategy_error = Some(e);
+                    self.synth_game.valid_robot_moves(&self.state)[0]
+                }
+            },
+        };
+
+        // get next state from game
+        self.state = self.synth_game.apply_robot_move(&self.state, robot_move);
+        self.robot_move = robot_move;
+
+        // update advisers
+        self.advisers.clear_all();
+        self.advisers
+            .safety
+            .extend_from_slice(self.strategy.safety_advisers(&self.state));
+        self.advisers
+            .fairness
+            .extend_from_slice(self.strategy.fairness_advisers(&self.state));
+
+        // if we are in strict adviser condition, compute the next move to be shown
+        if matches!(self.adviser_mode, AdviserMode::NextMove) {
+            self.advisers.next_move = next_move_advice(
+                &self.layout,
+                &self.human,
+                &self.advisers,
+                &self.synth_game.graph.human_ap,
+            );
+        }
+    }
+}
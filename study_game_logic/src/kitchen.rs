@@ -0,0 +1,387 @@
+use serde::Deserialize;
+use study_shared_types::InteractState;
+
+use crate::{
+    layout::{Agent, KitchenLayout, StationKind},
+    recipe::Recipe,
+    NextMove,
+};
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "[usize; 2]")]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Position {
+    pub fn is_equal(&self, coords: (usize, usize)) -> bool {
+        self.x == coords.0 && self.y == coords.1
+    }
+}
+
+impl From<(usize, usize)> for Position {
+    fn from((x, y): (usize, usize)) -> Self {
+        Position { x, y }
+    }
+}
+
+impl From<[usize; 2]> for Position {
+    fn from([x, y]: [usize; 2]) -> Self {
+        Position { x, y }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interact {
+    No,
+    In(Position),
+    Out(Position),
+    Stay(Position),
+}
+
+impl Interact {
+    /// Whether the actor is at the counter during this step.
+    pub fn is_active(&self) -> bool {
+        matches!(self, Interact::In(_) | Interact::Stay(_))
+    }
+}
+
+impl From<&Interact> for InteractState {
+    fn from(interact: &Interact) -> Self {
+        match interact {
+            Interact::No => InteractState::No,
+            Interact::In(_) => InteractState::In,
+            Interact::Out(_) => InteractState::Out,
+            Interact::Stay(_) => InteractState::Stay,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct BurgerProgress {
+    pub assembled: u32,
+    /// in the order they were taken
+    pub taken: Vec<String>,
+}
+
+impl BurgerProgress {
+    pub fn has(&self, ingredient: &str) -> bool {
+        self.taken.iter().any(|i| i == ingredient)
+    }
+
+    pub fn ready(&self, recipe: &Recipe) -> bool {
+        recipe.ingredients.iter().all(|i| self.has(i))
+    }
+
+    pub fn make_burger(&mut self, recipe: &Recipe) -> bool {
+        if self.ready(recipe) {
+            self.assembled += 1;
+            self.reset();
+            return true;
+        }
+        false
+    }
+
+    pub fn reset(&mut self) {
+        self.taken.clear();
+    }
+
+    /// Whether the recipe asks for the ingredient now, it is neither taken yet nor waiting for
+    /// another ingredient.
+    pub fn can_take(&self, recipe: &Recipe, ingredient: &str) -> bool {
+        recipe.contains(ingredient)
+            && !self.has(ingredient)
+            && recipe.required_before(ingredient).all(|i| self.has(i))
+    }
+
+    /// Takes the ingredient if the recipe asks for it now.
+    pub fn take(&mut self, recipe: &Recipe, ingredient: &str) {
+        if self.can_take(recipe, ingredient) {
+            self.taken.push(ingredient.to_owned());
+        }
+    }
+}
+
+/// The human or the robot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    pub pos: Position,
+    pub interact: Interact,
+    pub progress: BurgerProgress,
+}
+
+impl Actor {
+    pub fn new(start: Position) -> Self {
+        Actor {
+            pos: start,
+            interact: Interact::No,
+            progress: BurgerProgress::default(),
+        }
+    }
+
+    /// Interacting takes two steps, one to reach over the counter and one to come back.
+    fn update_interact(&mut self, layout: &KitchenLayout, agent: Agent, next_move: NextMove) {
+        self.interact = match self.interact {
+            // starting interaction mode
+            Interact::No | Interact::Out(_) => {
+                if next_move == NextMove::Interact {
+                    Interact::In(interacting_pos(layout, agent, self.pos))
+                } else {
+                    Interact::No
+                }
+            }
+            // ending or staying in interaction mode
+            Interact::In(ip) | Interact::Stay(ip) => {
+                if next_move == NextMove::Interact {
+                    Interact::Out(ip)
+                } else {
+                    Interact::Stay(ip)
+                }
+            }
+        };
+    }
+}
+
+/// Applies both moves at once. Returns whether the human and the robot finished a burger.
+pub fn resolve_moves(
+    layout: &KitchenLayout,
+    human: &mut Actor,
+    robot: &mut Actor,
+    human_move: NextMove,
+    robot_move: NextMove,
+) -> (bool, bool) {
+    human.update_interact(layout, Agent::Human, human_move);
+    robot.update_interact(layout, Agent::Robot, robot_move);
+
+    let mut human_burger = false;
+    if human.interact.is_active() {
+        human_burger =
+            update_burger_status(layout, Agent::Human, &mut human.progress, human.pos, robot);
+    }
+
+    let mut robot_burger = false;
+    if robot.interact.is_active() {
+        robot_burger =
+            update_burger_status(layout, Agent::Robot, &mut robot.progress, robot.pos, human);
+    }
+
+    human.pos = next_pos_from_move(&human.pos, human_move);
+    robot.pos = next_pos_from_move(&robot.pos, robot_move);
+
+    (human_burger, robot_burger)
+}
+
+/// The moves the map allows: walking onto a neighbouring floor tile, idling, and interacting
+/// while standing at one of the agent's stations. Once reaching over the counter, the agent
+/// has to come back first.
+pub fn valid_moves(
+    layout: &KitchenLayout,
+    agent: Agent,
+    cur_pos: Position,
+    interact: &Interact,
+) -> Vec<NextMove> {
+    // if we just went into interaction, we can only finish it
+    if interact.is_active() {
+        return vec![NextMove::Interact];
+    }
+
+    let mut moves = vec![NextMove::Idle];
+    if layout.station_at(agent, cur_pos).is_some() {
+        moves.push(NextMove::Interact);
+    }
+    for next_move in [
+        NextMove::Up,
+        NextMove::Down,
+        NextMove::Left,
+        NextMove::Right,
+    ] {
+        if layout
+            .neighbour(cur_pos, next_move)
+            .is_some_and(|next_pos| layout.is_floor(next_pos))
+        {
+            moves.push(next_move);
+        }
+    }
+    moves
+}
+
+/// The move meant by a direction key. Walking into the counter of the agent's station
+/// interacts with it, and while reaching over a counter every key brings the agent back.
+pub fn contextual_move(
+    layout: &KitchenLayout,
+    agent: Agent,
+    cur_pos: Position,
+    interact: &Interact,
+    pressed: NextMove,
+) -> NextMove {
+    if interact.is_active() {
+        return NextMove::Interact;
+    }
+    match layout.station_at(agent, cur_pos) {
+        Some(station) if layout.neighbour(cur_pos, pressed) == Some(station.counter) => {
+            NextMove::Interact
+        }
+        _ => pressed,
+    }
+}
+
+/// Whether the agent reaches over its delivery counter, i.e. delivers in this step.
+pub fn is_delivering(layout: &KitchenLayout, agent: Agent, actor: &Actor) -> bool {
+    actor.interact.is_active()
+        && layout
+            .station_at(agent, actor.pos)
+            .is_some_and(|s| s.kind == StationKind::Delivery)
+}
+
+/// The agent's next move on the way to delivering: coming back from a counter, walking a
+/// shortest way to its delivery station and interacting there. Only `options` are considered,
+/// `None` if none of them gets the agent closer.
+pub fn delivery_move(
+    layout: &KitchenLayout,
+    agent: Agent,
+    cur_pos: Position,
+    interacting: bool,
+    options: &[NextMove],
+) -> Option<NextMove> {
+    let interact = options
+        .contains(&NextMove::Interact)
+        .then_some(NextMove::Interact);
+    if interacting {
+        return interact;
+    }
+    let deliveries: Vec<Position> = layout
+        .stations_of(agent)
+        .filter(|s| s.kind == StationKind::Delivery)
+        .map(|s| s.pos)
+        .collect();
+    if deliveries.contains(&cur_pos) {
+        return interact;
+    }
+
+    let distances = layout.floor_distances(&deliveries);
+    let here = *distances.get(&cur_pos)?;
+    options
+        .iter()
+        .filter_map(|&m| {
+            let pos = layout.neighbour(cur_pos, m)?;
+            Some((m, *distances.get(&pos)?))
+        })
+        .filter(|&(_, distance)| distance < here)
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(m, _)| m)
+}
+
+/// The direction key that interacts with the counter of the agent's station at `cur_pos`.
+pub fn counter_direction(
+    layout: &KitchenLayout,
+    agent: Agent,
+    cur_pos: Position,
+) -> Option<NextMove> {
+    let counter = layout.station_at(agent, cur_pos)?.counter;
+    [
+        NextMove::Up,
+        NextMove::Down,
+        NextMove::Left,
+        NextMove::Right,
+    ]
+    .into_iter()
+    .find(|&m| layout.neighbour(cur_pos, m) == Some(counter))
+}
+
+/// The atomic propositions of the human, in the order of `guard_ap`.
+pub fn obs_from_pos(
+    layout: &KitchenLayout,
+    pos: Position,
+    interact: &Interact,
+    guard_ap: &[String],
+) -> String {
+    let station = layout
+        .station_at(Agent::Human, pos)
+        .filter(|_| interact.is_active());
+    let mut obs = String::with_capacity(guard_ap.len());
+    for ap in guard_ap {
+        let holds = match (station, layout.proposition(ap)) {
+            (Some(station), Some(proposition)) => proposition.holds_at(station),
+            _ => false,
+        };
+        obs.push(if holds { '1' } else { '0' });
+    }
+    obs
+}
+
+pub fn interacting_pos(layout: &KitchenLayout, agent: Agent, cur_pos: Position) -> Position {
+    layout
+        .station_at(agent, cur_pos)
+        .map(|station| station.counter)
+        .expect("No interacting_pos found!")
+}
+
+/// Takes the ingredient of the station the agent interacts with if its recipe asks for it, or
+/// delivers.
+pub fn update_burger_status(
+    layout: &KitchenLayout,
+    agent: Agent,
+    burger_progress: &mut BurgerProgress,
+    cur_pos: Position,
+    other: &Actor,
+) -> bool {
+    let station = match layout.station_at(agent, cur_pos) {
+        Some(station) => station,
+        None => return false,
+    };
+    let recipe = layout.recipe(agent);
+    let ingredient = match &station.kind {
+        StationKind::Ingredient(ingredient) => ingredient,
+        StationKind::Delivery => return burger_progress.make_burger(recipe),
+    };
+
+    // is the other agent at the same counter?
+    let other_station = layout
+        .station_at(agent.other(), other.pos)
+        .filter(|s| other.interact.is_active() && s.counter == station.counter);
+    let takes = if station.needs_help {
+        other_station.is_some()
+    } else {
+        // if the other agent needs help, we help instead of taking
+        !other_station.is_some_and(|s| s.needs_help)
+    };
+    if takes {
+        burger_progress.take(recipe, ingredient);
+    }
+    false
+}
+
+/// Where a move takes an agent standing at `pos`, and whether it then reaches over the counter.
+/// A move onto a tile that is not floor leaves the agent where it is.
+pub fn location_after(
+    layout: &KitchenLayout,
+    (pos, interacting): (Position, bool),
+    next_move: NextMove,
+) -> (Position, bool) {
+    match next_move {
+        NextMove::Interact => (pos, !interacting),
+        _ if interacting => (pos, true),
+        _ => (
+            layout
+                .neighbour(pos, next_move)
+                .filter(|&next_pos| layout.is_floor(next_pos))
+                .unwrap_or(pos),
+            false,
+        ),
+    }
+}
+
+/// Where a move takes an agent. A move off the edge of the grid is blocked and leaves the agent
+/// where it is, which tiles are floor is up to `valid_moves`.
+pub fn next_pos_from_move(cur_pos: &Position, next_move: NextMove) -> Position {
+    let Position { x, y } = *cur_pos;
+    let next_pos = match next_move {
+        NextMove::Idle | NextMove::Interact => Some((x, y)),
+        NextMove::Up => Some((x, y + 1)),
+        NextMove::Down => y.checked_sub(1).map(|y| (x, y)),
+        NextMove::Left => x.checked_sub(1).map(|x| (x, y)),
+        NextMove::Right => Some((x + 1, y)),
+    };
+    next_pos.map_or(*cur_pos, Position::from)
+}
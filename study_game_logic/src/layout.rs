@@ -0,0 +1,225 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    kitchen::Position,
+    proposition::Proposition,
+    recipe::{Recipe, Recipes},
+    NextMove,
+};
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Agent {
+    Human,
+    Robot,
+}
+
+impl Agent {
+    pub fn other(self) -> Agent {
+        match self {
+            Agent::Human => Agent::Robot,
+            Agent::Robot => Agent::Human,
+        }
+    }
+}
+
+impl Display for Agent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Agent::Human => write!(f, "human"),
+            Agent::Robot => write!(f, "robot"),
+        }
+    }
+}
+
+/// What a counter is for, written as the ingredient's name or `"delivery"`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String")]
+pub enum StationKind {
+    Ingredient(String),
+    Delivery,
+}
+
+impl From<String> for StationKind {
+    fn from(name: String) -> Self {
+        if name == "delivery" {
+            StationKind::Delivery
+        } else {
+            StationKind::Ingredient(name)
+        }
+    }
+}
+
+impl Display for StationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StationKind::Ingredient(name) => write!(f, "{}", name),
+            StationKind::Delivery => write!(f, "delivery"),
+        }
+    }
+}
+
+/// A counter as one agent uses it. Human and robot stations may share a counter.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Station {
+    pub kind: StationKind,
+    pub agent: Agent,
+    /// where the agent stands
+    pub pos: Position,
+    /// the tile the agent reaches over to when interacting
+    pub counter: Position,
+    /// The ingredient is only taken while the other agent interacts with the same counter,
+    /// who helps instead of taking it themselves.
+    #[serde(default)]
+    pub needs_help: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StartPositions {
+    pub human: Position,
+    pub robot: Position,
+}
+
+/// The kitchen map, coordinates are `[x, y]` with `y` pointing up. The actors walk on the floor
+/// tiles, interact with the counters of their stations and collect what their recipe asks for.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub struct KitchenLayout {
+    /// columns and rows of the grid
+    pub size: [usize; 2],
+    pub floor: Vec<Position>,
+    pub start: StartPositions,
+    pub stations: Vec<Station>,
+    pub recipes: Recipes,
+    /// the atomic propositions of the game graph for both agents
+    pub propositions: Vec<Proposition>,
+}
+
+impl KitchenLayout {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn width(&self) -> usize {
+        self.size[0]
+    }
+
+    pub fn height(&self) -> usize {
+        self.size[1]
+    }
+
+    pub fn start(&self, agent: Agent) -> Position {
+        match agent {
+            Agent::Human => self.start.human,
+            Agent::Robot => self.start.robot,
+        }
+    }
+
+    pub fn recipe(&self, agent: Agent) -> &Recipe {
+        match agent {
+            Agent::Human => &self.recipes.human,
+            Agent::Robot => &self.recipes.robot,
+        }
+    }
+
+    pub fn proposition(&self, name: &str) -> Option<&Proposition> {
+        self.propositions.iter().find(|p| p.name == name)
+    }
+
+    pub fn is_floor(&self, pos: Position) -> bool {
+        self.floor.contains(&pos)
+    }
+
+    pub fn stations_of(&self, agent: Agent) -> impl Iterator<Item = &Station> {
+        self.stations.iter().filter(move |s| s.agent == agent)
+    }
+
+    /// The station the agent works at when standing on `pos`.
+    pub fn station_at(&self, agent: Agent, pos: Position) -> Option<&Station> {
+        self.stations_of(agent).find(|s| s.pos == pos)
+    }
+
+    /// What the counter at `pos` holds, if it is one.
+    pub fn counter_at(&self, pos: Position) -> Option<&StationKind> {
+        self.stations
+            .iter()
+            .find(|s| s.counter == pos)
+            .map(|s| &s.kind)
+    }
+
+    /// The floor tiles the agent can walk to from its start.
+    pub fn reachable_cells(&self, agent: Agent) -> Vec<Position> {
+        let start = self.start(agent);
+        if !self.is_floor(start) {
+            return Vec::new();
+        }
+        let mut cells = vec![start];
+        let mut next = 0;
+        while next < cells.len() {
+            let pos = cells[next];
+            next += 1;
+            for step in [
+                NextMove::Up,
+                NextMove::Down,
+                NextMove::Left,
+                NextMove::Right,
+            ] {
+                if let Some(neighbour) = self.neighbour(pos, step) {
+                    if self.is_floor(neighbour) && !cells.contains(&neighbour) {
+                        cells.push(neighbour);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    /// Steps over the floor to the nearest of the targets, for every tile they can be reached
+    /// from.
+    pub fn floor_distances(&self, targets: &[Position]) -> HashMap<Position, usize> {
+        self.floor_distances_where(targets, |_| true)
+    }
+
+    /// Like `floor_distances`, but only over the tiles `passable` allows.
+    pub fn floor_distances_where(
+        &self,
+        targets: &[Position],
+        passable: impl Fn(Position) -> bool,
+    ) -> HashMap<Position, usize> {
+        let mut distances: HashMap<Position, usize> = targets.iter().map(|&t| (t, 0)).collect();
+        let mut queue: VecDeque<Position> = targets.iter().copied().collect();
+        while let Some(pos) = queue.pop_front() {
+            let distance = distances[&pos];
+            for step in [
+                NextMove::Up,
+                NextMove::Down,
+                NextMove::Left,
+                NextMove::Right,
+            ] {
+                if let Some(next) = self.neighbour(pos, step) {
+                    if self.is_floor(next) && passable(next) && !distances.contains_key(&next) {
+                        distances.insert(next, distance + 1);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        distances
+    }
+
+    /// The cell a move leads to, if it is on the grid.
+    pub fn neighbour(&self, pos: Position, next_move: NextMove) -> Option<Position> {
+        let (x, y) = match next_move {
+            NextMove::Idle | NextMove::Interact => (pos.x, pos.y),
+            NextMove::Up => (pos.x, pos.y + 1),
+            NextMove::Down => (pos.x, pos.y.checked_sub(1)?),
+            NextMove::Left => (pos.x.checked_sub(1)?, pos.y),
+            NextMove::Right => (pos.x + 1, pos.y),
+        };
+        (x < self.width() && y < self.height()).then_some(Position { x, y })
+    }
+}
@@ -0,0 +1,27 @@
+//! The rules of the kitchen study, without any rendering. The frontend drives a
+//! [`KitchenGame`] with the participant's moves, and the same game can be simulated without a
+//! window, e.g. to check synthesized strategies in bulk.
+
+mod advisers;
+mod binary;
+mod game;
+mod kitchen;
+mod layout;
+mod moves;
+mod proposition;
+mod recipe;
+mod synth;
+mod synthesis;
+mod validate;
+
+pub use advisers::*;
+pub use binary::*;
+pub use game::*;
+pub use kitchen::*;
+pub use layout::*;
+pub use moves::*;
+pub use proposition::*;
+pub use recipe::*;
+pub use synth::*;
+pub use synthesis::*;
+pub use validate::*;
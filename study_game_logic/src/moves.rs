@@ -0,0 +1,50 @@
+use std::{error::Error, fmt::Display, str::FromStr};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum NextMove {
+    #[default]
+    Idle,
+    Up,
+    Down,
+    Left,
+    Right,
+    Interact,
+}
+
+impl FromStr for NextMove {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "idle" => Ok(NextMove::Idle),
+            "up" => Ok(NextMove::Up),
+            "down" => Ok(NextMove::Down),
+            "left" => Ok(NextMove::Left),
+            "right" => Ok(NextMove::Right),
+            "interact" => Ok(NextMove::Interact),
+            _ => Err(ParseMoveError),
+        }
+    }
+}
+
+impl Display for NextMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NextMove::Idle => write!(f, "idle"),
+            NextMove::Up => write!(f, "up"),
+            NextMove::Down => write!(f, "down"),
+            NextMove::Left => write!(f, "left"),
+            NextMove::Right => write!(f, "right"),
+            NextMove::Interact => write!(f, "interact"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseMoveError;
+impl Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Something went wrong parsing a Move!")
+    }
+}
+impl Error for ParseMoveError {}
@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+use crate::layout::{Agent, Station, StationKind};
+
+/// An atomic proposition of the game graph, e.g. `patty_h`. It holds while its agent interacts
+/// at one of its stations of the kind `station`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Proposition {
+    pub name: String,
+    pub agent: Agent,
+    pub station: StationKind,
+    /// shown in the advice, relative to the assets folder
+    #[serde(default)]
+    pub sprite: Option<String>,
+    /// shown if there is no sprite
+    pub label: String,
+}
+
+impl Proposition {
+    pub fn holds_at(&self, station: &Station) -> bool {
+        station.agent == self.agent && station.kind == self.station
+    }
+}
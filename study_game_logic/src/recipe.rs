@@ -0,0 +1,30 @@
+use serde::Deserialize;
+
+/// What an agent collects before delivering a burger.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+    pub ingredients: Vec<String>,
+    /// `[before, after]` pairs, `after` can only be taken once `before` is
+    #[serde(default)]
+    pub order: Vec<[String; 2]>,
+}
+
+impl Recipe {
+    pub fn contains(&self, ingredient: &str) -> bool {
+        self.ingredients.iter().any(|i| i == ingredient)
+    }
+
+    /// The ingredients that have to be taken before this one.
+    pub fn required_before<'a>(&'a self, ingredient: &'a str) -> impl Iterator<Item = &'a str> {
+        self.order
+            .iter()
+            .filter(move |[_, after]| after == ingredient)
+            .map(|[before, _]| before.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Recipes {
+    pub human: Recipe,
+    pub robot: Recipe,
+}
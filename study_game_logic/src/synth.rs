@@ -0,0 +1,445 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    fmt::Display,
+};
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    kitchen::{location_after, Position},
+    layout::{Agent, KitchenLayout, StationKind},
+    NextMove,
+};
+
+pub type GraphState = (String, String, String);
+pub type Guards = Vec<String>;
+
+// `NodeData::player`
+pub(crate) const PROB_PLAYER: u8 = 0;
+pub(crate) const ROBOT_PLAYER: u8 = 1;
+pub(crate) const HUMAN_PLAYER: u8 = 2;
+
+/// The robot strategy and the advisers, as written by the synthesis tool.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(from = "StrategyData")]
+pub struct Strategy {
+    /// the robot's move in each of its states
+    pub strat: Vec<(GraphState, String)>,
+    pub safety_adv: Vec<(GraphState, Guards)>,
+    pub fairness_adv: Vec<(GraphState, Guards)>,
+    pub guard_ap: Vec<String>,
+    moves_by_state: HashMap<GraphState, String>,
+    safety_by_state: HashMap<GraphState, Vec<Guards>>,
+    fairness_by_state: HashMap<GraphState, Vec<Guards>>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct StrategyData {
+    pub(crate) strat: Vec<(GraphState, String)>,
+    pub(crate) safety_adv: Vec<(GraphState, Guards)>,
+    pub(crate) fairness_adv: Vec<(GraphState, Guards)>,
+    pub(crate) guard_ap: Vec<String>,
+}
+
+impl From<StrategyData> for Strategy {
+    fn from(data: StrategyData) -> Self {
+        Strategy {
+            moves_by_state: data.strat.iter().cloned().collect(),
+            safety_by_state: advisers_by_state(&data.safety_adv),
+            fairness_by_state: advisers_by_state(&data.fairness_adv),
+            strat: data.strat,
+            safety_adv: data.safety_adv,
+            fairness_adv: data.fairness_adv,
+            guard_ap: data.guard_ap,
+        }
+    }
+}
+
+fn advisers_by_state(advisers: &[(GraphState, Guards)]) -> HashMap<GraphState, Vec<Guards>> {
+    let mut by_state: HashMap<GraphState, Vec<Guards>> = HashMap::new();
+    for (state, guards) in advisers {
+        by_state
+            .entry(state.clone())
+            .or_default()
+            .push(guards.clone());
+    }
+    by_state
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StrategyError {
+    /// the strategy has no move for this robot state
+    MissingState(GraphState),
+    InvalidMove {
+        state: GraphState,
+        next_move: String,
+    },
+}
+
+impl Display for StrategyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StrategyError::MissingState(state) => {
+                write!(f, "the strategy has no move for state {:?}", state)
+            }
+            StrategyError::InvalidMove { state, next_move } => write!(
+                f,
+                "the strategy has invalid move {:?} for state {:?}",
+                next_move, state
+            ),
+        }
+    }
+}
+
+impl Error for StrategyError {}
+
+impl Strategy {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json).map_err(|e| {
+            // strategies keyed by Python tuple strings, e.g. "('20', '1')"
+            let legacy = serde_json::from_str::<serde_json::Value>(json)
+                .is_ok_and(|value| value["strat"].is_object());
+            if legacy {
+                serde::de::Error::custom(
+                    "the strategy uses the old tuple string keys, convert it with migrate_strategy",
+                )
+            } else {
+                e
+            }
+        })
+    }
+
+    pub fn next_move(&self, state: &GraphState) -> Result<NextMove, StrategyError> {
+        let next_move = self
+            .moves_by_state
+            .get(state)
+            .ok_or_else(|| StrategyError::MissingState(state.clone()))?;
+        next_move.parse().map_err(|_| StrategyError::InvalidMove {
+            state: state.clone(),
+            next_move: next_move.clone(),
+        })
+    }
+
+    /// The safety advisers active in `state`, each with the guards it forbids.
+    pub fn safety_advisers(&self, state: &GraphState) -> &[Guards] {
+        self.safety_by_state.get(state).map_or(&[], Vec::as_slice)
+    }
+
+    /// The fairness advisers active in `state`, each with the guards it asks for.
+    pub fn fairness_advisers(&self, state: &GraphState) -> &[Guards] {
+        self.fairness_by_state.get(state).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The game graph the strategy was synthesized on, in the networkx node-link format.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(from = "SynthGameData")]
+pub struct SynthGame {
+    pub directed: bool,
+    pub multigraph: bool,
+    pub graph: Graph,
+    pub nodes: Vec<NodeData>,
+    pub links: Vec<LinkData>,
+    /// indices into `links` by source state, so a step only looks at the outgoing edges
+    out_edges: HashMap<GraphState, Vec<usize>>,
+    accepting: HashSet<GraphState>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SynthGameData {
+    pub(crate) directed: bool,
+    pub(crate) multigraph: bool,
+    pub(crate) graph: Graph,
+    pub(crate) nodes: Vec<NodeData>,
+    pub(crate) links: Vec<LinkData>,
+}
+
+impl From<SynthGameData> for SynthGame {
+    fn from(data: SynthGameData) -> Self {
+        let mut out_edges: HashMap<GraphState, Vec<usize>> = HashMap::new();
+        for (i, edge) in data.links.iter().enumerate() {
+            out_edges.entry(edge.source.clone()).or_default().push(i);
+        }
+
+        SynthGame {
+            accepting: data.graph.acc.iter().cloned().collect(),
+            directed: data.directed,
+            multigraph: data.multigraph,
+            graph: data.graph,
+            nodes: data.nodes,
+            links: data.links,
+            out_edges,
+        }
+    }
+}
+
+impl SynthGame {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn is_accepting(&self, state: &GraphState) -> bool {
+        self.accepting.contains(state)
+    }
+
+    /// All edges leaving `state`.
+    pub fn edges_from<'a>(&'a self, state: &GraphState) -> impl Iterator<Item = &'a LinkData> {
+        self.out_edges
+            .get(state)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.links[i])
+    }
+
+    pub fn apply_robot_move(&self, cur_state: &GraphState, next_move: NextMove) -> GraphState {
+        for edge in self.edges_from(cur_state) {
+            if edge.act() == next_move {
+                return edge.target.clone();
+            }
+        }
+
+        panic!("No next state found!");
+    }
+
+    pub fn apply_human_obs(&self, cur_state: &GraphState, obs: &str) -> GraphState {
+        for edge in self.edges_from(cur_state) {
+            if edge
+                .guards
+                .as_ref()
+                .expect("Not a human state")
+                .iter()
+                .any(|g| g == obs)
+            {
+                return edge.target.clone();
+            }
+        }
+
+        panic!("No next state found!");
+    }
+
+    /// Samples one of the outcomes of a probabilistic state according to the edge probabilities.
+    pub fn skip_prob_state<R: Rng + ?Sized>(
+        &self,
+        prob_state: &GraphState,
+        rng: &mut R,
+    ) -> &LinkData {
+        let edges: Vec<&LinkData> = self.edges_from(prob_state).collect();
+        let (last, rest) = edges.split_last().expect("No next state found!");
+        if rest.is_empty() {
+            return last;
+        }
+
+        let mut sample: f32 = rng.gen();
+        for edge in rest {
+            sample -= edge.prob.unwrap_or(0.0);
+            if sample < 0.0 {
+                return edge;
+            }
+        }
+        // the probabilities may not add up to exactly 1.0
+        last
+    }
+
+    /// The state in which the task starts over once it was completed in `state`: the initial
+    /// task with the robot where it is. Games without such a state start over from `init`.
+    pub fn restart_state(&self, state: &GraphState) -> GraphState {
+        let restarted = (
+            state.0.clone(),
+            self.graph.init.1.clone(),
+            self.graph.init.2.clone(),
+        );
+        if self.out_edges.contains_key(&restarted) {
+            restarted
+        } else {
+            self.graph.init.clone()
+        }
+    }
+
+    pub fn valid_robot_moves(&self, cur_state: &GraphState) -> Vec<NextMove> {
+        self.edges_from(cur_state).map(LinkData::act).collect()
+    }
+}
+
+/// Where the robot stands and whether it reaches over the counter.
+pub type RobotLocation = (Position, bool);
+
+/// Where the robot is in the states of a game, worked out by playing the robot's moves on the
+/// layout from its start, so it does not depend on how the states are named. In human and
+/// probabilistic states the robot has chosen its move, but not made it yet.
+#[derive(Debug, Default)]
+pub struct RobotLocations {
+    locations: HashMap<GraphState, RobotLocation>,
+    pending: HashMap<GraphState, NextMove>,
+    /// states the moves put the robot into different places
+    conflicts: Vec<GraphState>,
+}
+
+impl RobotLocations {
+    pub fn new(game: &SynthGame, layout: &KitchenLayout) -> Self {
+        let players: HashMap<&GraphState, u8> =
+            game.nodes.iter().map(|n| (&n.id, n.player)).collect();
+        let delivering = |(pos, interacting): RobotLocation| {
+            interacting
+                && layout
+                    .station_at(Agent::Robot, pos)
+                    .is_some_and(|s| s.kind == StationKind::Delivery)
+        };
+
+        let mut found = RobotLocations::default();
+        let mut queue = VecDeque::new();
+        found.place(
+            &game.graph.init,
+            (layout.start(Agent::Robot), false),
+            None,
+            &mut queue,
+        );
+        // outcomes of a probabilistic state are placed by other paths where possible, the
+        // others are taken to be where the chosen move takes the robot
+        let mut outcomes: VecDeque<(GraphState, RobotLocation)> = VecDeque::new();
+        loop {
+            while let Some(state) = queue.pop_front() {
+                let location = found.locations[&state];
+                let pending = found.pending.get(&state).copied();
+                match players.get(&state) {
+                    Some(&ROBOT_PLAYER) => {
+                        for edge in game.edges_from(&state) {
+                            if let Some(Ok(next_move)) = edge.act.as_deref().map(str::parse) {
+                                found.place(&edge.target, location, Some(next_move), &mut queue);
+                            }
+                        }
+                        // the task starts over where the robot delivered
+                        if game.is_accepting(&state) && delivering(location) {
+                            let restarted = game.restart_state(&state);
+                            if restarted != game.graph.init {
+                                found.place(&restarted, location, None, &mut queue);
+                            }
+                        }
+                    }
+                    Some(&HUMAN_PLAYER) => {
+                        for edge in game.edges_from(&state) {
+                            found.place(&edge.target, location, pending, &mut queue);
+                        }
+                    }
+                    Some(&PROB_PLAYER) => {
+                        let moved =
+                            pending.map_or(location, |m| location_after(layout, location, m));
+                        let edges: Vec<&LinkData> = game.edges_from(&state).collect();
+                        match edges[..] {
+                            [edge] => found.place(&edge.target, moved, None, &mut queue),
+                            _ => outcomes.extend(edges.iter().map(|e| (e.target.clone(), moved))),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            match outcomes.pop_front() {
+                Some((state, location)) => {
+                    if !found.locations.contains_key(&state) {
+                        found.place(&state, location, None, &mut queue);
+                    }
+                }
+                None => break,
+            }
+        }
+        found
+    }
+
+    fn place(
+        &mut self,
+        state: &GraphState,
+        location: RobotLocation,
+        pending: Option<NextMove>,
+        queue: &mut VecDeque<GraphState>,
+    ) {
+        match self.locations.get(state) {
+            Some(&known) => {
+                let differs = known != location || self.pending.get(state) != pending.as_ref();
+                if differs && !self.conflicts.contains(state) {
+                    self.conflicts.push(state.clone());
+                }
+            }
+            None => {
+                self.locations.insert(state.clone(), location);
+                if let Some(next_move) = pending {
+                    self.pending.insert(state.clone(), next_move);
+                }
+                queue.push_back(state.clone());
+            }
+        }
+    }
+
+    /// `None` for states the robot cannot get to.
+    pub fn get(&self, state: &GraphState) -> Option<RobotLocation> {
+        self.locations.get(state).copied()
+    }
+
+    /// The move the robot has chosen in a human or probabilistic state.
+    pub fn pending_move(&self, state: &GraphState) -> Option<NextMove> {
+        self.pending.get(state).copied()
+    }
+
+    pub fn conflicts(&self) -> &[GraphState] {
+        &self.conflicts
+    }
+}
+
+/// The move that takes the robot from one location to the other, if a single move does.
+pub fn move_between(from: RobotLocation, to: RobotLocation) -> Option<NextMove> {
+    let ((from_pos, from_interacting), (to_pos, to_interacting)) = (from, to);
+    if from_pos == to_pos {
+        return Some(if from_interacting != to_interacting {
+            NextMove::Interact
+        } else {
+            NextMove::Idle
+        });
+    }
+    if from_interacting || to_interacting {
+        return None;
+    }
+
+    let dx = to_pos.x as isize - from_pos.x as isize;
+    let dy = to_pos.y as isize - from_pos.y as isize;
+    match (dx, dy) {
+        (0, 1) => Some(NextMove::Up),
+        (0, -1) => Some(NextMove::Down),
+        (-1, 0) => Some(NextMove::Left),
+        (1, 0) => Some(NextMove::Right),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Graph {
+    pub acc: Vec<GraphState>,
+    pub init: GraphState,
+    pub human_ap: Vec<String>,
+    pub mdp_ap: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct NodeData {
+    pub player: u8,
+    pub ap: Option<String>,
+    pub id: GraphState,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct LinkData {
+    pub act: Option<String>,
+    pub guards: Option<Guards>,
+    pub prob: Option<f32>,
+    pub source: GraphState,
+    pub target: GraphState,
+}
+
+impl LinkData {
+    pub fn act(&self) -> NextMove {
+        self.act
+            .as_ref()
+            .expect("Edge without act label!")
+            .parse()
+            .expect("Edge with invalid act label!")
+    }
+}
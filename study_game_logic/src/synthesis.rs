@@ -0,0 +1,456 @@
+//! Synthesizes the robot's strategy and the advisers from the game graph, so a kitchen can be
+//! tried out without the external synthesis tool. The robot has to visit accepting states again
+//! and again. The human is asked for as little as that needs: the safety advisers forbid what
+//! would make the task impossible, and the fairness advisers ask for progress where the robot
+//! cannot force it. Probabilistic outcomes are taken to happen eventually if tried often enough.
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    error::Error,
+    fmt::Display,
+};
+
+use crate::{
+    advisers::obs_match_guard,
+    layout::KitchenLayout,
+    synth::{
+        GraphState, Guards, LinkData, Strategy, StrategyData, SynthGame, HUMAN_PLAYER, PROB_PLAYER,
+        ROBOT_PLAYER,
+    },
+    validate::human_observations,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SynthesisError {
+    /// not even a cooperating human lets the robot complete the task from the start
+    InitLosing(GraphState),
+}
+
+impl Display for SynthesisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SynthesisError::InitLosing(state) => {
+                write!(
+                    f,
+                    "the task cannot be completed from the initial state {:?}",
+                    state
+                )
+            }
+        }
+    }
+}
+
+impl Error for SynthesisError {}
+
+/// A strategy for the robot with the advisers the human has to follow for it to win, in the
+/// format of the synthesis tool. Guards only name observations the human can make in the
+/// layout, anything else is left open with `X`.
+pub fn synthesize(game: &SynthGame, layout: &KitchenLayout) -> Result<Strategy, SynthesisError> {
+    let possible = human_observations(layout, &game.graph.human_ap);
+    synthesize_for(game, &possible)
+}
+
+/// `synthesize` for a human who can make the `possible` observations.
+fn synthesize_for(
+    game: &SynthGame,
+    possible: &BTreeSet<String>,
+) -> Result<Strategy, SynthesisError> {
+    let arena = Arena::new(game, possible);
+    let region = cooperative_region(&arena);
+    match arena.index(&game.graph.init) {
+        Some(init) if region[init] => {}
+        _ => return Err(SynthesisError::InitLosing(game.graph.init.clone())),
+    }
+    let (rank, live) = progress_ranks(&arena, &region);
+
+    let mut strat = Vec::new();
+    let mut safety_adv = Vec::new();
+    let mut fairness_adv = Vec::new();
+    for v in (0..arena.states.len()).filter(|&v| region[v]) {
+        let state = arena.states[v];
+        match arena.players[v] {
+            ROBOT_PLAYER => {
+                // all states of the region are ranked
+                let best = arena.succ[v]
+                    .iter()
+                    .filter(|&&(w, _)| region[w])
+                    .min_by_key(|&&(w, _)| rank[w]);
+                if let Some(act) = best.and_then(|(_, edge)| edge.act.clone()) {
+                    strat.push((state.clone(), act));
+                }
+            }
+            HUMAN_PLAYER => {
+                let observations = |keep: &dyn Fn(usize) -> bool| {
+                    arena.succ[v]
+                        .iter()
+                        .filter(|&&(w, _)| keep(w))
+                        .flat_map(|(_, edge)| edge.guards.iter().flatten())
+                        .filter(|obs| possible.contains(*obs))
+                        .map(String::as_str)
+                        .collect::<BTreeSet<&str>>()
+                };
+                let unsafe_obs = observations(&|w| !region[w]);
+                if !unsafe_obs.is_empty() {
+                    let safe_obs = observations(&|w| region[w]);
+                    safety_adv.push((state.clone(), cover(&unsafe_obs, &safe_obs)));
+                }
+                if live[v] {
+                    let progress =
+                        |w: usize| matches!((rank[w], rank[v]), (Some(r), Some(own)) if r < own);
+                    let progress_obs = observations(&progress);
+                    let other_obs = observations(&|w| !progress(w));
+                    fairness_adv.push((state.clone(), cover(&progress_obs, &other_obs)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Strategy::from(StrategyData {
+        strat,
+        safety_adv,
+        fairness_adv,
+        guard_ap: game.graph.human_ap.clone(),
+    }))
+}
+
+/// The game graph by index. Human edges without an observation the human can make are left
+/// out, the human can never take them.
+struct Arena<'a> {
+    states: Vec<&'a GraphState>,
+    indices: HashMap<&'a GraphState, usize>,
+    players: Vec<u8>,
+    accepting: Vec<bool>,
+    succ: Vec<Vec<(usize, &'a LinkData)>>,
+    pred: Vec<Vec<usize>>,
+}
+
+impl<'a> Arena<'a> {
+    fn new(game: &'a SynthGame, possible: &BTreeSet<String>) -> Self {
+        let states: Vec<&GraphState> = game.nodes.iter().map(|n| &n.id).collect();
+        let mut arena = Arena {
+            indices: states.iter().enumerate().map(|(i, &s)| (s, i)).collect(),
+            players: game.nodes.iter().map(|n| n.player).collect(),
+            accepting: states.iter().map(|s| game.is_accepting(s)).collect(),
+            succ: vec![Vec::new(); states.len()],
+            pred: vec![Vec::new(); states.len()],
+            states,
+        };
+        for edge in &game.links {
+            let (v, w) = match (arena.index(&edge.source), arena.index(&edge.target)) {
+                (Some(v), Some(w)) => (v, w),
+                _ => continue,
+            };
+            let takeable = arena.players[v] != HUMAN_PLAYER
+                || edge.guards.iter().flatten().any(|g| possible.contains(g));
+            if takeable {
+                arena.succ[v].push((w, edge));
+                arena.pred[w].push(v);
+            }
+        }
+        arena
+    }
+
+    fn index(&self, state: &GraphState) -> Option<usize> {
+        self.indices.get(state).copied()
+    }
+
+    /// Whether `v` can move into `target` without leaving `region`, which a probabilistic
+    /// state cannot promise if any of its outcomes is outside.
+    fn enters(&self, v: usize, target: &[bool], region: &[bool]) -> bool {
+        if !region[v] {
+            return false;
+        }
+        let succ = &self.succ[v];
+        if self.players[v] == PROB_PLAYER && !succ.iter().all(|&(w, _)| region[w]) {
+            return false;
+        }
+        succ.iter().any(|&(w, _)| target[w])
+    }
+}
+
+/// The states from which robot and human together can visit accepting states again and again.
+fn cooperative_region(arena: &Arena) -> Vec<bool> {
+    let n = arena.states.len();
+    let mut region = vec![true; n];
+    loop {
+        let mut reaching = vec![false; n];
+        let mut queue: Vec<usize> = (0..n)
+            .filter(|&v| arena.accepting[v] && arena.enters(v, &region, &region))
+            .collect();
+        for &v in &queue {
+            reaching[v] = true;
+        }
+        while let Some(v) = queue.pop() {
+            for &u in &arena.pred[v] {
+                if !reaching[u] && arena.enters(u, &reaching, &region) {
+                    reaching[u] = true;
+                    queue.push(u);
+                }
+            }
+        }
+        if reaching == region {
+            return region;
+        }
+        region = reaching;
+    }
+}
+
+/// Orders the states of the region by how they lead to an accepting state, the robot moves to
+/// lower ranks. Where neither the robot nor chance makes progress, the human is asked for it in
+/// as few states as possible: those are marked live, their progress is taking an edge to a lower
+/// rank.
+fn progress_ranks(arena: &Arena, region: &[bool]) -> (Vec<Option<usize>>, Vec<bool>) {
+    let n = arena.states.len();
+    let mut rank: Vec<Option<usize>> = vec![None; n];
+    let mut live = vec![false; n];
+    let mut next_rank = 0;
+    let mut frontier: Vec<usize> = (0..n)
+        .filter(|&v| region[v] && arena.accepting[v])
+        .collect();
+    while !frontier.is_empty() {
+        let mut queue = VecDeque::new();
+        for v in frontier {
+            rank[v] = Some(next_rank);
+            next_rank += 1;
+            queue.push_back(v);
+        }
+        // what the robot can force from here on, the human only takes edges inside the region
+        while let Some(v) = queue.pop_front() {
+            for &u in &arena.pred[v] {
+                if rank[u].is_some() || !region[u] {
+                    continue;
+                }
+                let forced = arena.players[u] == ROBOT_PLAYER
+                    || arena.succ[u]
+                        .iter()
+                        .all(|&(w, _)| !region[w] || rank[w].is_some());
+                if forced {
+                    rank[u] = Some(next_rank);
+                    next_rank += 1;
+                    queue.push_back(u);
+                }
+            }
+        }
+
+        let pending = |player: u8| -> Vec<usize> {
+            (0..n)
+                .filter(|&u| region[u] && rank[u].is_none() && arena.players[u] == player)
+                .filter(|&u| arena.succ[u].iter().any(|&(w, _)| rank[w].is_some()))
+                .collect()
+        };
+        frontier = pending(PROB_PLAYER);
+        if frontier.is_empty() {
+            // one at a time, the robot may be able to force the others from there
+            let closest = pending(HUMAN_PLAYER)
+                .into_iter()
+                .min_by_key(|&u| arena.succ[u].iter().filter_map(|&(w, _)| rank[w]).min());
+            if let Some(u) = closest {
+                live[u] = true;
+                frontier.push(u);
+            }
+        }
+    }
+    (rank, live)
+}
+
+/// Guards matching every observation of `on` and none of `off`. Observations in neither may
+/// match as well, which keeps the guards short.
+fn cover(on: &BTreeSet<&str>, off: &BTreeSet<&str>) -> Guards {
+    let mut candidates: Vec<String> = Vec::new();
+    for obs in on {
+        let mut guard: Vec<char> = obs.chars().collect();
+        for i in 0..guard.len() {
+            let value = guard[i];
+            guard[i] = 'X';
+            let widened: String = guard.iter().collect();
+            if off.iter().any(|o| obs_match_guard(o, &widened)) {
+                guard[i] = value;
+            }
+        }
+        let guard: String = guard.into_iter().collect();
+        if !candidates.contains(&guard) {
+            candidates.push(guard);
+        }
+    }
+
+    // greedily take the guard matching most of the observations left
+    let mut left: Vec<&str> = on.iter().copied().collect();
+    let mut guards = Vec::new();
+    while !left.is_empty() {
+        let best = candidates
+            .iter()
+            .max_by_key(|g| left.iter().filter(|o| obs_match_guard(o, g)).count())
+            .expect("every observation has a guard")
+            .clone();
+        left.retain(|o| !obs_match_guard(o, &best));
+        guards.push(best);
+    }
+    guards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::{Graph, NodeData, SynthGameData};
+
+    fn state(name: &str) -> GraphState {
+        (name.to_owned(), String::new(), String::new())
+    }
+
+    /// A game over two human propositions whose first node is the initial one. The label of a
+    /// link is the act of a robot, the observation of a human or the probability of chance.
+    fn game(nodes: &[(&str, u8)], links: &[(&str, &str, &str)], acc: &[&str]) -> SynthGame {
+        let player = |name: &str| nodes.iter().find(|n| n.0 == name).unwrap().1;
+        let links = links
+            .iter()
+            .map(|&(source, target, label)| {
+                let mut link = LinkData {
+                    act: None,
+                    guards: None,
+                    prob: None,
+                    source: state(source),
+                    target: state(target),
+                };
+                match player(source) {
+                    ROBOT_PLAYER => link.act = Some(label.to_owned()),
+                    HUMAN_PLAYER => link.guards = Some(vec![label.to_owned()]),
+                    _ => link.prob = Some(label.parse().unwrap()),
+                }
+                link
+            })
+            .collect();
+        SynthGame::from(SynthGameData {
+            directed: true,
+            multigraph: false,
+            graph: Graph {
+                acc: acc.iter().map(|s| state(s)).collect(),
+                init: state(nodes[0].0),
+                human_ap: vec!["a".to_owned(), "b".to_owned()],
+                mdp_ap: Vec::new(),
+            },
+            nodes: nodes
+                .iter()
+                .map(|&(name, player)| NodeData {
+                    player,
+                    ap: None,
+                    id: state(name),
+                })
+                .collect(),
+            links,
+        })
+    }
+
+    fn possible() -> BTreeSet<String> {
+        ["00", "01", "10", "11"].map(String::from).into()
+    }
+
+    #[test]
+    fn states_that_cannot_win_are_left_out() {
+        let game = game(
+            &[
+                ("start", ROBOT_PLAYER),
+                ("done", ROBOT_PLAYER),
+                ("stuck", ROBOT_PLAYER),
+            ],
+            &[
+                ("start", "done", "up"),
+                ("start", "stuck", "down"),
+                ("done", "start", "idle"),
+                ("stuck", "stuck", "idle"),
+            ],
+            &["done"],
+        );
+        let possible = possible();
+        let arena = Arena::new(&game, &possible);
+        assert_eq!(cooperative_region(&arena), [true, true, false]);
+    }
+
+    #[test]
+    fn chance_may_lose_the_game() {
+        // only the detour is certain to get the robot to the accepting state
+        let game = game(
+            &[
+                ("start", ROBOT_PLAYER),
+                ("gamble", PROB_PLAYER),
+                ("detour", PROB_PLAYER),
+                ("done", ROBOT_PLAYER),
+                ("stuck", ROBOT_PLAYER),
+            ],
+            &[
+                ("start", "gamble", "up"),
+                ("start", "detour", "down"),
+                ("gamble", "done", "0.9"),
+                ("gamble", "stuck", "0.1"),
+                ("detour", "done", "0.5"),
+                ("detour", "start", "0.5"),
+                ("done", "start", "idle"),
+                ("stuck", "stuck", "idle"),
+            ],
+            &["done"],
+        );
+        let possible = possible();
+        let arena = Arena::new(&game, &possible);
+        let region = cooperative_region(&arena);
+        assert_eq!(region, [true, false, true, true, false]);
+
+        let strategy = synthesize_on(&game);
+        assert_eq!(strategy.strat[0], (state("start"), "down".to_owned()));
+    }
+
+    #[test]
+    fn one_of_equally_close_human_states_is_asked_for_progress() {
+        // either way the human has to help, once they do in one the robot can force the other
+        let game = game(
+            &[
+                ("start", ROBOT_PLAYER),
+                ("left", HUMAN_PLAYER),
+                ("right", HUMAN_PLAYER),
+                ("done", ROBOT_PLAYER),
+            ],
+            &[
+                ("start", "left", "left"),
+                ("start", "right", "right"),
+                ("left", "done", "10"),
+                ("left", "start", "00"),
+                ("right", "done", "10"),
+                ("right", "start", "00"),
+                ("done", "start", "idle"),
+            ],
+            &["done"],
+        );
+        let possible = possible();
+        let arena = Arena::new(&game, &possible);
+        let region = cooperative_region(&arena);
+        let (rank, live) = progress_ranks(&arena, &region);
+        assert!(rank.iter().all(Option::is_some));
+        assert_eq!(live, [false, true, false, false]);
+        assert!(rank[2] > rank[0] && rank[0] > rank[1] && rank[1] > rank[3]);
+
+        let strategy = synthesize_on(&game);
+        assert_eq!(strategy.strat[0], (state("start"), "left".to_owned()));
+        assert_eq!(
+            strategy.fairness_adv,
+            [(state("left"), vec!["1X".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn cover_takes_the_fewest_guards() {
+        let set = |obs: &[&'static str]| obs.iter().copied().collect::<BTreeSet<&str>>();
+        // one guard matches all four observations
+        let guards = cover(&set(&["100", "101", "110", "111"]), &set(&["000", "011"]));
+        assert_eq!(guards, ["1XX"]);
+        // no single guard matches both without matching `00`
+        let guards = cover(&set(&["10", "01"]), &set(&["00"]));
+        assert_eq!(guards, ["1X", "X1"]);
+        // observations in neither set may match
+        let guards = cover(&set(&["11"]), &set(&["00"]));
+        assert_eq!(guards, ["X1"]);
+    }
+
+    /// `synthesize` for a human who can make every observation.
+    fn synthesize_on(game: &SynthGame) -> Strategy {
+        synthesize_for(game, &possible()).unwrap()
+    }
+}
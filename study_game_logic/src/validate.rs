@@ -0,0 +1,830 @@
+//! Checks that the game graph, the strategy and the kitchen layout fit together. A mismatch would
+//! otherwise only show up as a panic somewhere in the middle of a study session.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Display,
+};
+
+use crate::{
+    advisers::obs_match_guard,
+    kitchen::{delivery_move, obs_from_pos, valid_moves, BurgerProgress, Interact, Position},
+    layout::{Agent, KitchenLayout, StationKind},
+    synth::{
+        move_between, GraphState, Guards, RobotLocation, RobotLocations, Strategy, StrategyError,
+        SynthGame, HUMAN_PLAYER, PROB_PLAYER, ROBOT_PLAYER,
+    },
+    NextMove,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsistencyError {
+    InitMissing(GraphState),
+    AcceptingStateMissing(GraphState),
+    EdgeStateMissing {
+        source: GraphState,
+        target: GraphState,
+    },
+    /// a robot state without any move
+    RobotStuck(GraphState),
+    InvalidAct {
+        state: GraphState,
+        act: Option<String>,
+    },
+    /// a state the robot's moves put it into different places
+    RobotLocationConflict(GraphState),
+    /// a robot move the kitchen does not allow where the state puts the robot
+    RobotMoveOffMap {
+        state: GraphState,
+        next_move: NextMove,
+    },
+    /// an accepting state in which no robot move leads towards its delivery station
+    NoDeliveryPath(GraphState),
+    HumanEdgeWithoutGuards(GraphState),
+    MalformedGuard {
+        state: GraphState,
+        guard: String,
+    },
+    UncoveredObservations {
+        state: GraphState,
+        observations: Vec<String>,
+    },
+    ProbStateStuck(GraphState),
+    MissingProbability {
+        state: GraphState,
+        target: GraphState,
+    },
+    ProbabilitiesDontSum {
+        state: GraphState,
+        sum: f32,
+    },
+    /// an outcome the robot cannot be shown to reach with a single move
+    UnreachableOutcome {
+        state: GraphState,
+        target: GraphState,
+    },
+    GuardApMismatch {
+        guard_ap: Vec<String>,
+        human_ap: Vec<String>,
+    },
+    /// an atomic proposition of the game graph the layout has no entry for
+    UnknownProposition {
+        agent: Agent,
+        name: String,
+    },
+    StrategyStateMissing(GraphState),
+    InvalidStrategyMove {
+        state: GraphState,
+        next_move: String,
+    },
+    /// a robot state the game can reach, but the strategy has no move for
+    NoStrategyMove(GraphState),
+    /// the strategy's move is not an edge of the game
+    StrategyMoveImpossible {
+        state: GraphState,
+        next_move: NextMove,
+    },
+    AdviserStateMissing {
+        adviser: &'static str,
+        state: GraphState,
+    },
+    MalformedAdviserGuard {
+        adviser: &'static str,
+        state: GraphState,
+        guard: String,
+    },
+    /// an adviser without any guard, which never or always holds
+    AdviserWithoutGuards {
+        adviser: &'static str,
+        state: GraphState,
+    },
+    OutOfBounds(Position),
+    /// a position an agent stands on
+    NotFloor(Position),
+    CounterIsFloor(Position),
+    CounterNotAdjacent {
+        kind: StationKind,
+        agent: Agent,
+        pos: Position,
+        counter: Position,
+    },
+    DuplicateStation {
+        agent: Agent,
+        pos: Position,
+    },
+    StationUnreachable {
+        agent: Agent,
+        pos: Position,
+    },
+    DeliveryMissing(Agent),
+    /// a station that needs help, but the other agent has no station at its counter
+    HelperMissing {
+        kind: StationKind,
+        counter: Position,
+    },
+    NoStationForIngredient {
+        agent: Agent,
+        ingredient: String,
+    },
+    /// an ordering constraint on an ingredient the recipe does not ask for
+    UnknownOrderIngredient {
+        agent: Agent,
+        ingredient: String,
+    },
+    DuplicateProposition(String),
+    /// a proposition that can never hold
+    PropositionWithoutStation {
+        name: String,
+        agent: Agent,
+        station: StationKind,
+    },
+    /// the ordering constraints of the recipe can never all be met
+    RecipeOrderCycle(Agent),
+}
+
+impl Display for ConsistencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsistencyError::InitMissing(s) => write!(f, "initial state {:?} is not a node", s),
+            ConsistencyError::AcceptingStateMissing(s) => {
+                write!(f, "accepting state {:?} is not a node", s)
+            }
+            ConsistencyError::EdgeStateMissing { source, target } => {
+                write!(
+                    f,
+                    "edge {:?} -> {:?} leads to an unknown node",
+                    source, target
+                )
+            }
+            ConsistencyError::RobotStuck(s) => write!(f, "robot state {:?} has no moves", s),
+            ConsistencyError::InvalidAct { state, act } => {
+                write!(f, "robot state {:?} has an edge with act {:?}", state, act)
+            }
+            ConsistencyError::RobotLocationConflict(s) => write!(
+                f,
+                "the robot's moves put it into different places in state {:?}",
+                s
+            ),
+            ConsistencyError::RobotMoveOffMap { state, next_move } => write!(
+                f,
+                "robot state {:?} has move {}, which the kitchen does not allow there",
+                state, next_move
+            ),
+            ConsistencyError::NoDeliveryPath(s) => {
+                write!(
+                    f,
+                    "accepting state {:?} has no move towards the delivery",
+                    s
+                )
+            }
+            ConsistencyError::HumanEdgeWithoutGuards(s) => {
+                write!(f, "human state {:?} has an edge without guards", s)
+            }
+            ConsistencyError::MalformedGuard { state, guard } => {
+                write!(
+                    f,
+                    "human state {:?} has a malformed guard {:?}",
+                    state, guard
+                )
+            }
+            ConsistencyError::UncoveredObservations {
+                state,
+                observations,
+            } => write!(
+                f,
+                "human state {:?} has no edge for observations {}",
+                state,
+                observations.join(", ")
+            ),
+            ConsistencyError::ProbStateStuck(s) => {
+                write!(f, "probabilistic state {:?} has no edges", s)
+            }
+            ConsistencyError::MissingProbability { state, target } => write!(
+                f,
+                "probabilistic edge {:?} -> {:?} has no probability",
+                state, target
+            ),
+            ConsistencyError::ProbabilitiesDontSum { state, sum } => write!(
+                f,
+                "probabilities of state {:?} sum to {} instead of 1",
+                state, sum
+            ),
+            ConsistencyError::UnreachableOutcome { state, target } => write!(
+                f,
+                "outcome {:?} of state {:?} is not one robot move away",
+                target, state
+            ),
+            ConsistencyError::GuardApMismatch { guard_ap, human_ap } => write!(
+                f,
+                "strategy guard_ap {:?} differs from game human_ap {:?}",
+                guard_ap, human_ap
+            ),
+            ConsistencyError::UnknownProposition { agent, name } => write!(
+                f,
+                "{} atomic proposition {:?} is not among the layout's propositions",
+                agent, name
+            ),
+            ConsistencyError::StrategyStateMissing(state) => {
+                write!(f, "strategy state {:?} is not a robot node", state)
+            }
+            ConsistencyError::InvalidStrategyMove { state, next_move } => write!(
+                f,
+                "strategy state {:?} has invalid move {:?}",
+                state, next_move
+            ),
+            ConsistencyError::NoStrategyMove(state) => {
+                write!(f, "reachable robot state {:?} has no strategy move", state)
+            }
+            ConsistencyError::StrategyMoveImpossible { state, next_move } => write!(
+                f,
+                "strategy move {} is not possible in state {:?}",
+                next_move, state
+            ),
+            ConsistencyError::AdviserStateMissing { adviser, state } => {
+                write!(f, "{} adviser state {:?} is not a node", adviser, state)
+            }
+            ConsistencyError::MalformedAdviserGuard {
+                adviser,
+                state,
+                guard,
+            } => write!(
+                f,
+                "{} adviser in state {:?} has a malformed guard {:?}",
+                adviser, state, guard
+            ),
+            ConsistencyError::AdviserWithoutGuards { adviser, state } => {
+                write!(f, "{} adviser in state {:?} has no guards", adviser, state)
+            }
+            ConsistencyError::OutOfBounds(pos) => {
+                write!(f, "tile ({}, {}) is outside of the kitchen", pos.x, pos.y)
+            }
+            ConsistencyError::NotFloor(pos) => {
+                write!(f, "tile ({}, {}) should be floor", pos.x, pos.y)
+            }
+            ConsistencyError::CounterIsFloor(pos) => {
+                write!(f, "counter ({}, {}) is a floor tile", pos.x, pos.y)
+            }
+            ConsistencyError::CounterNotAdjacent {
+                kind,
+                agent,
+                pos,
+                counter,
+            } => write!(
+                f,
+                "{} {} station at ({}, {}) is not next to its counter ({}, {})",
+                agent, kind, pos.x, pos.y, counter.x, counter.y
+            ),
+            ConsistencyError::DuplicateStation { agent, pos } => write!(
+                f,
+                "{} has more than one station at ({}, {})",
+                agent, pos.x, pos.y
+            ),
+            ConsistencyError::StationUnreachable { agent, pos } => write!(
+                f,
+                "{} cannot walk to their station at ({}, {})",
+                agent, pos.x, pos.y
+            ),
+            ConsistencyError::DeliveryMissing(agent) => {
+                write!(f, "{} has no delivery station", agent)
+            }
+            ConsistencyError::HelperMissing { kind, counter } => write!(
+                f,
+                "nobody can help at the {} counter ({}, {})",
+                kind, counter.x, counter.y
+            ),
+            ConsistencyError::NoStationForIngredient { agent, ingredient } => write!(
+                f,
+                "{} has no station for the {} of their recipe",
+                agent, ingredient
+            ),
+            ConsistencyError::UnknownOrderIngredient { agent, ingredient } => write!(
+                f,
+                "the order of the {} recipe names {}, which it does not ask for",
+                agent, ingredient
+            ),
+            ConsistencyError::DuplicateProposition(name) => {
+                write!(f, "atomic proposition {:?} is listed twice", name)
+            }
+            ConsistencyError::PropositionWithoutStation {
+                name,
+                agent,
+                station,
+            } => write!(
+                f,
+                "atomic proposition {:?} needs a {} station of the {}",
+                name, station, agent
+            ),
+            ConsistencyError::RecipeOrderCycle(agent) => {
+                write!(f, "the order of the {} recipe has a cycle", agent)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConsistencyError {}
+
+/// All problems found, empty if the study can run with this data.
+pub fn validate(
+    game: &SynthGame,
+    strategy: &Strategy,
+    layout: &KitchenLayout,
+) -> Vec<ConsistencyError> {
+    let mut errors = validate_game(game, layout);
+    errors.extend(validate_strategy(game, strategy, layout));
+    errors.extend(validate_layout(layout));
+    errors
+}
+
+pub fn validate_game(game: &SynthGame, layout: &KitchenLayout) -> Vec<ConsistencyError> {
+    let mut errors = Vec::new();
+    let states: HashSet<&GraphState> = game.nodes.iter().map(|n| &n.id).collect();
+
+    if !states.contains(&game.graph.init) {
+        errors.push(ConsistencyError::InitMissing(game.graph.init.clone()));
+    }
+    for state in &game.graph.acc {
+        if !states.contains(state) {
+            errors.push(ConsistencyError::AcceptingStateMissing(state.clone()));
+        }
+    }
+    for edge in &game.links {
+        if !states.contains(&edge.source) || !states.contains(&edge.target) {
+            errors.push(ConsistencyError::EdgeStateMissing {
+                source: edge.source.clone(),
+                target: edge.target.clone(),
+            });
+        }
+    }
+    for (agent, aps) in [
+        (Agent::Human, &game.graph.human_ap),
+        (Agent::Robot, &game.graph.mdp_ap),
+    ] {
+        for ap in aps {
+            if !layout.proposition(ap).is_some_and(|p| p.agent == agent) {
+                errors.push(ConsistencyError::UnknownProposition {
+                    agent,
+                    name: ap.clone(),
+                });
+            }
+        }
+    }
+
+    let locations = RobotLocations::new(game, layout);
+    errors.extend(
+        locations
+            .conflicts()
+            .iter()
+            .cloned()
+            .map(ConsistencyError::RobotLocationConflict),
+    );
+
+    let observations = human_observations(layout, &game.graph.human_ap);
+    for node in &game.nodes {
+        let state = &node.id;
+        match node.player {
+            ROBOT_PLAYER => {
+                let mut has_moves = false;
+                let legal = robot_moves_on_map(layout, locations.get(state));
+                for edge in game.edges_from(state) {
+                    has_moves = true;
+                    match edge.act.as_deref().map(str::parse::<NextMove>) {
+                        Some(Ok(next_move)) => {
+                            if legal.as_ref().is_some_and(|m| !m.contains(&next_move)) {
+                                errors.push(ConsistencyError::RobotMoveOffMap {
+                                    state: state.clone(),
+                                    next_move,
+                                });
+                            }
+                        }
+                        _ => errors.push(ConsistencyError::InvalidAct {
+                            state: state.clone(),
+                            act: edge.act.clone(),
+                        }),
+                    }
+                }
+                if !has_moves {
+                    errors.push(ConsistencyError::RobotStuck(state.clone()));
+                }
+            }
+            HUMAN_PLAYER => {
+                let mut covered = HashSet::new();
+                for edge in game.edges_from(state) {
+                    let guards = match &edge.guards {
+                        Some(guards) => guards,
+                        None => {
+                            errors.push(ConsistencyError::HumanEdgeWithoutGuards(state.clone()));
+                            continue;
+                        }
+                    };
+                    for guard in guards {
+                        if !is_observation(guard, game.graph.human_ap.len()) {
+                            errors.push(ConsistencyError::MalformedGuard {
+                                state: state.clone(),
+                                guard: guard.clone(),
+                            });
+                        }
+                        covered.insert(guard.as_str());
+                    }
+                }
+                let uncovered: Vec<String> = observations
+                    .iter()
+                    .filter(|obs| !covered.contains(obs.as_str()))
+                    .cloned()
+                    .collect();
+                if !uncovered.is_empty() {
+                    errors.push(ConsistencyError::UncoveredObservations {
+                        state: state.clone(),
+                        observations: uncovered,
+                    });
+                }
+            }
+            PROB_PLAYER => errors.extend(validate_prob_state(game, &locations, state)),
+            _ => {}
+        }
+    }
+    errors
+}
+
+/// Tolerance for probabilities that were rounded when the game was written.
+const PROB_EPSILON: f32 = 1e-4;
+
+fn validate_prob_state(
+    game: &SynthGame,
+    locations: &RobotLocations,
+    state: &GraphState,
+) -> Vec<ConsistencyError> {
+    let mut errors = Vec::new();
+    let edges: Vec<_> = game.edges_from(state).collect();
+    if edges.is_empty() {
+        errors.push(ConsistencyError::ProbStateStuck(state.clone()));
+        return errors;
+    }
+
+    let mut sum = 0.0;
+    for edge in &edges {
+        match edge.prob {
+            Some(prob) => sum += prob,
+            None => errors.push(ConsistencyError::MissingProbability {
+                state: state.clone(),
+                target: edge.target.clone(),
+            }),
+        }
+        // with a single outcome, the robot always makes the chosen move
+        let unreachable = match (locations.get(state), locations.get(&edge.target)) {
+            (Some(from), Some(to)) => move_between(from, to).is_none(),
+            // states the robot cannot get to
+            _ => false,
+        };
+        if edges.len() > 1 && unreachable {
+            errors.push(ConsistencyError::UnreachableOutcome {
+                state: state.clone(),
+                target: edge.target.clone(),
+            });
+        }
+    }
+    if (sum - 1.0).abs() > PROB_EPSILON {
+        errors.push(ConsistencyError::ProbabilitiesDontSum {
+            state: state.clone(),
+            sum,
+        });
+    }
+    errors
+}
+
+pub fn validate_strategy(
+    game: &SynthGame,
+    strategy: &Strategy,
+    layout: &KitchenLayout,
+) -> Vec<ConsistencyError> {
+    let mut errors = Vec::new();
+
+    if strategy.guard_ap != game.graph.human_ap {
+        errors.push(ConsistencyError::GuardApMismatch {
+            guard_ap: strategy.guard_ap.clone(),
+            human_ap: game.graph.human_ap.clone(),
+        });
+    }
+
+    let robot_states: HashSet<&GraphState> = game
+        .nodes
+        .iter()
+        .filter(|n| n.player == ROBOT_PLAYER)
+        .map(|n| &n.id)
+        .collect();
+    for (state, next_move) in &strategy.strat {
+        if !robot_states.contains(state) {
+            errors.push(ConsistencyError::StrategyStateMissing(state.clone()));
+        }
+        if next_move.parse::<NextMove>().is_err() {
+            errors.push(ConsistencyError::InvalidStrategyMove {
+                state: state.clone(),
+                next_move: next_move.clone(),
+            });
+        }
+    }
+    errors.extend(validate_reachable(game, strategy, layout));
+
+    let states: HashSet<&GraphState> = game.nodes.iter().map(|n| &n.id).collect();
+    let ap_count = game.graph.human_ap.len();
+    for (adviser, advisers) in [
+        ("safety", &strategy.safety_adv),
+        ("fairness", &strategy.fairness_adv),
+    ] {
+        for (state, guards) in advisers {
+            errors.extend(validate_adviser(adviser, state, guards, &states, ap_count));
+        }
+    }
+    errors
+}
+
+/// Follows the strategy from the initial state, the human may make any observation the safety
+/// advisers allow and chance may take any edge. Every robot state on the way needs a move.
+fn validate_reachable(
+    game: &SynthGame,
+    strategy: &Strategy,
+    layout: &KitchenLayout,
+) -> Vec<ConsistencyError> {
+    let mut errors = Vec::new();
+    let players: HashMap<&GraphState, u8> = game.nodes.iter().map(|n| (&n.id, n.player)).collect();
+    let locations = RobotLocations::new(game, layout);
+
+    let mut visited: HashSet<GraphState> = HashSet::new();
+    let mut queue = VecDeque::from([game.graph.init.clone()]);
+    while let Some(state) = queue.pop_front() {
+        if !visited.insert(state.clone()) {
+            continue;
+        }
+        match players.get(&state) {
+            Some(&ROBOT_PLAYER) => {}
+            Some(&HUMAN_PLAYER) => {
+                // observations the safety advisers forbid reset the game
+                let safety = strategy.safety_advisers(&state);
+                let allowed = |obs: &String| {
+                    !safety
+                        .iter()
+                        .flatten()
+                        .any(|g| g.len() == obs.len() && obs_match_guard(obs, g))
+                };
+                for edge in game.edges_from(&state) {
+                    if edge.guards.iter().flatten().any(allowed) {
+                        queue.push_back(edge.target.clone());
+                    }
+                }
+                continue;
+            }
+            _ => {
+                queue.extend(game.edges_from(&state).map(|e| e.target.clone()));
+                continue;
+            }
+        }
+
+        // the same choice `KitchenGame` makes
+        let mut state = state;
+        let mut delivery = None;
+        if game.is_accepting(&state) {
+            let (pos, interacting) = match locations.get(&state) {
+                Some(location) => location,
+                // without knowing where the robot is, any move may be on its way to deliver
+                None => {
+                    queue.extend(game.edges_from(&state).map(|e| e.target.clone()));
+                    continue;
+                }
+            };
+            let delivering = interacting
+                && layout
+                    .station_at(Agent::Robot, pos)
+                    .is_some_and(|s| s.kind == StationKind::Delivery);
+            if delivering {
+                state = game.restart_state(&state);
+            } else {
+                let options = game.valid_robot_moves(&state);
+                delivery = delivery_move(layout, Agent::Robot, pos, interacting, &options);
+                if delivery.is_none() {
+                    errors.push(ConsistencyError::NoDeliveryPath(state.clone()));
+                }
+            }
+        }
+        let next_move = match delivery {
+            Some(next_move) => next_move,
+            None => match strategy.next_move(&state) {
+                Ok(next_move) => next_move,
+                Err(StrategyError::MissingState(state)) => {
+                    errors.push(ConsistencyError::NoStrategyMove(state));
+                    continue;
+                }
+                // reported with the strategy
+                Err(StrategyError::InvalidMove { .. }) => continue,
+            },
+        };
+        let from = state;
+
+        let mut targets = game
+            .edges_from(&from)
+            .filter(|e| e.act.as_deref().and_then(|act| act.parse().ok()) == Some(next_move))
+            .map(|e| e.target.clone())
+            .peekable();
+        if targets.peek().is_none() {
+            errors.push(ConsistencyError::StrategyMoveImpossible {
+                state: from,
+                next_move,
+            });
+            continue;
+        }
+        queue.extend(targets);
+    }
+    errors
+}
+
+fn validate_adviser(
+    adviser: &'static str,
+    state: &GraphState,
+    guards: &Guards,
+    states: &HashSet<&GraphState>,
+    ap_count: usize,
+) -> Vec<ConsistencyError> {
+    let mut errors = Vec::new();
+    if !states.contains(state) {
+        errors.push(ConsistencyError::AdviserStateMissing {
+            adviser,
+            state: state.clone(),
+        });
+    }
+    if guards.is_empty() {
+        errors.push(ConsistencyError::AdviserWithoutGuards {
+            adviser,
+            state: state.clone(),
+        });
+    }
+    for guard in guards {
+        let valid = guard.len() == ap_count && guard.chars().all(|c| matches!(c, '0' | '1' | 'X'));
+        if !valid {
+            errors.push(ConsistencyError::MalformedAdviserGuard {
+                adviser,
+                state: state.clone(),
+                guard: guard.clone(),
+            });
+        }
+    }
+    errors
+}
+
+pub fn validate_layout(layout: &KitchenLayout) -> Vec<ConsistencyError> {
+    let mut errors = Vec::new();
+    let in_bounds = |pos: Position| pos.x < layout.width() && pos.y < layout.height();
+
+    for &pos in &layout.floor {
+        if !in_bounds(pos) {
+            errors.push(ConsistencyError::OutOfBounds(pos));
+        }
+    }
+    let mut standing = vec![layout.start.human, layout.start.robot];
+    standing.extend(layout.stations.iter().map(|s| s.pos));
+    let mut reported = HashSet::new();
+    for pos in standing {
+        if !layout.is_floor(pos) && reported.insert(pos) {
+            errors.push(ConsistencyError::NotFloor(pos));
+        }
+    }
+    for agent in [Agent::Human, Agent::Robot] {
+        let reachable = layout.reachable_cells(agent);
+        for station in layout.stations_of(agent) {
+            if layout.is_floor(station.pos) && !reachable.contains(&station.pos) {
+                errors.push(ConsistencyError::StationUnreachable {
+                    agent,
+                    pos: station.pos,
+                });
+            }
+        }
+    }
+
+    let mut stations = HashSet::new();
+    for station in &layout.stations {
+        if !stations.insert((station.agent, station.pos)) {
+            errors.push(ConsistencyError::DuplicateStation {
+                agent: station.agent,
+                pos: station.pos,
+            });
+        }
+        let counter = station.counter;
+        if !in_bounds(counter) {
+            errors.push(ConsistencyError::OutOfBounds(counter));
+        } else if layout.is_floor(counter) {
+            errors.push(ConsistencyError::CounterIsFloor(counter));
+        }
+        if station.pos.x.abs_diff(counter.x) + station.pos.y.abs_diff(counter.y) != 1 {
+            errors.push(ConsistencyError::CounterNotAdjacent {
+                kind: station.kind.clone(),
+                agent: station.agent,
+                pos: station.pos,
+                counter,
+            });
+        }
+        let helped = layout
+            .stations_of(station.agent.other())
+            .any(|s| s.counter == counter);
+        if station.needs_help && !helped {
+            errors.push(ConsistencyError::HelperMissing {
+                kind: station.kind.clone(),
+                counter,
+            });
+        }
+    }
+
+    for agent in [Agent::Human, Agent::Robot] {
+        if !layout
+            .stations_of(agent)
+            .any(|s| s.kind == StationKind::Delivery)
+        {
+            errors.push(ConsistencyError::DeliveryMissing(agent));
+        }
+        errors.extend(validate_recipe(layout, agent));
+    }
+
+    let mut names = HashSet::new();
+    for proposition in &layout.propositions {
+        if !names.insert(&proposition.name) {
+            errors.push(ConsistencyError::DuplicateProposition(
+                proposition.name.clone(),
+            ));
+        }
+        if !layout.stations.iter().any(|s| proposition.holds_at(s)) {
+            errors.push(ConsistencyError::PropositionWithoutStation {
+                name: proposition.name.clone(),
+                agent: proposition.agent,
+                station: proposition.station.clone(),
+            });
+        }
+    }
+    errors
+}
+
+fn validate_recipe(layout: &KitchenLayout, agent: Agent) -> Vec<ConsistencyError> {
+    let mut errors = Vec::new();
+    let recipe = layout.recipe(agent);
+    for ingredient in &recipe.ingredients {
+        let kind = StationKind::Ingredient(ingredient.clone());
+        if !layout.stations_of(agent).any(|s| s.kind == kind) {
+            errors.push(ConsistencyError::NoStationForIngredient {
+                agent,
+                ingredient: ingredient.clone(),
+            });
+        }
+    }
+    for ingredient in recipe.order.iter().flatten() {
+        if !recipe.contains(ingredient) {
+            errors.push(ConsistencyError::UnknownOrderIngredient {
+                agent,
+                ingredient: ingredient.clone(),
+            });
+        }
+    }
+    if !errors.is_empty() {
+        return errors;
+    }
+
+    // take whatever can be taken until nothing changes, like an agent would
+    let mut progress = BurgerProgress::default();
+    while let Some(ingredient) = recipe
+        .ingredients
+        .iter()
+        .find(|i| progress.can_take(recipe, i))
+    {
+        progress.taken.push(ingredient.clone());
+    }
+    if !progress.ready(recipe) {
+        errors.push(ConsistencyError::RecipeOrderCycle(agent));
+    }
+    errors
+}
+
+/// The moves the kitchen allows where a robot state puts the robot, `None` for states the robot
+/// cannot get to.
+fn robot_moves_on_map(
+    layout: &KitchenLayout,
+    location: Option<RobotLocation>,
+) -> Option<Vec<NextMove>> {
+    let (pos, interacting) = location?;
+    Some(if interacting {
+        // the robot may also stay at the counter
+        vec![NextMove::Interact, NextMove::Idle]
+    } else {
+        valid_moves(layout, Agent::Robot, pos, &Interact::No)
+    })
+}
+
+/// Every observation the human can make, standing or interacting anywhere they can walk to.
+pub(crate) fn human_observations(layout: &KitchenLayout, human_ap: &[String]) -> BTreeSet<String> {
+    let mut observations = BTreeSet::new();
+    for pos in layout.reachable_cells(Agent::Human) {
+        observations.insert(obs_from_pos(layout, pos, &Interact::No, human_ap));
+        if let Some(station) = layout.station_at(Agent::Human, pos) {
+            let interact = Interact::In(station.counter);
+            observations.insert(obs_from_pos(layout, pos, &interact, human_ap));
+        }
+    }
+    observations
+}
+
+fn is_observation(guard: &str, ap_count: usize) -> bool {
+    guard.len() == ap_count && guard.chars().all(|c| matches!(c, '0' | '1'))
+}
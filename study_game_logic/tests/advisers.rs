@@ -0,0 +1,66 @@
+//! Advisers may hold several guards, and guards may ask for a proposition not to hold.
+
+mod common;
+
+use common::asset;
+use study_game_logic::{guard_literals, ActiveAdvisers, Strategy};
+
+fn guards(guards: &[&str]) -> Vec<String> {
+    guards.iter().map(|g| g.to_string()).collect()
+}
+
+#[test]
+fn negated_literals() {
+    assert_eq!(
+        guard_literals("X10X1").collect::<Vec<_>>(),
+        [(1, true), (2, false), (4, true)]
+    );
+
+    let advisers = ActiveAdvisers {
+        safety: vec![guards(&["1X0"])],
+        ..Default::default()
+    };
+    assert!(advisers.safety_violated("100"));
+    assert!(advisers.safety_violated("110"));
+    assert!(!advisers.safety_violated("101"));
+    assert!(!advisers.safety_violated("000"));
+}
+
+#[test]
+fn any_guard_of_an_adviser_holds() {
+    let advisers = ActiveAdvisers {
+        safety: vec![guards(&["1XX", "X1X"]), guards(&["XX1"])],
+        fairness: vec![guards(&["0X1", "010"])],
+        ..Default::default()
+    };
+    assert!(advisers.safety_violated("100"));
+    assert!(advisers.safety_violated("010"));
+    assert!(advisers.safety_violated("001"));
+    assert!(!advisers.safety_violated("000"));
+
+    assert!(advisers.fairness_met("001"));
+    assert!(advisers.fairness_met("010"));
+    assert!(!advisers.fairness_met("101"));
+    assert!(!advisers.fairness_met("000"));
+}
+
+#[test]
+fn advisers_keep_their_guards_apart() {
+    let mut json: serde_json::Value = serde_json::from_str(&asset("strat.json.strat")).unwrap();
+    let (state, first) = {
+        let entry = &json["safety_adv"][0];
+        (entry[0].clone(), entry[1][0].clone())
+    };
+    // a second adviser with two guards in the same state
+    json["safety_adv"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!([state, ["1XXXX", "X1XXX"]]));
+    let strategy: Strategy = serde_json::from_value(json).unwrap();
+
+    let state = serde_json::from_value(state).unwrap();
+    let advisers = strategy.safety_advisers(&state);
+    assert_eq!(advisers.len(), 2);
+    assert_eq!(advisers[0], [first.as_str().unwrap()]);
+    assert_eq!(advisers[1], guards(&["1XXXX", "X1XXX"]));
+}
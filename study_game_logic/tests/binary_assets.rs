@@ -0,0 +1,42 @@
+//! The frontend loads the binary assets, the JSON ones are edited. Both have to describe the
+//! same game, otherwise `convert_assets` was not run after a change.
+
+mod common;
+
+use std::fs;
+
+use common::{asset, asset_path};
+use study_game_logic::{BinaryError, Strategy, SynthGame};
+
+#[test]
+fn binary_game_matches_json() {
+    let json = SynthGame::from_json(&asset("game.json.game")).unwrap();
+    let binary = SynthGame::from_binary(&fs::read(asset_path("game.bin.game")).unwrap()).unwrap();
+    assert!(
+        json == binary,
+        "game.bin.game is out of date, run convert_assets"
+    );
+}
+
+#[test]
+fn binary_strategy_matches_json() {
+    let json = Strategy::from_json(&asset("strat.json.strat")).unwrap();
+    let binary = Strategy::from_binary(&fs::read(asset_path("strat.bin.strat")).unwrap()).unwrap();
+    assert!(
+        json == binary,
+        "strat.bin.strat is out of date, run convert_assets"
+    );
+}
+
+#[test]
+fn binary_assets_are_not_mixed_up() {
+    let game = fs::read(asset_path("game.bin.game")).unwrap();
+    assert!(matches!(
+        Strategy::from_binary(&game),
+        Err(BinaryError::WrongMagic)
+    ));
+    assert!(matches!(
+        SynthGame::from_binary(&game[..game.len() / 2]),
+        Err(BinaryError::Malformed(_))
+    ));
+}
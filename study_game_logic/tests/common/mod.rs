@@ -0,0 +1,45 @@
+//! The assets the frontend ships, shared by the integration tests. Each test binary only uses
+//! some of them.
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf, sync::Arc};
+
+use study_game_logic::{KitchenGame, KitchenLayout, Strategy, SynthGame};
+use study_shared_types::AdviserMode;
+
+pub fn asset_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../study_frontend/assets/data")
+        .join(name)
+}
+
+pub fn asset(name: &str) -> String {
+    fs::read_to_string(asset_path(name)).unwrap()
+}
+
+pub fn game() -> SynthGame {
+    SynthGame::from_json(&asset("game.json.game")).unwrap()
+}
+
+pub fn strategy() -> Strategy {
+    Strategy::from_json(&asset("strat.json.strat")).unwrap()
+}
+
+pub fn layout() -> KitchenLayout {
+    KitchenLayout::from_json(&asset("tiles.json.tiles")).unwrap()
+}
+
+pub fn shipped() -> (SynthGame, Strategy, KitchenLayout) {
+    (game(), strategy(), layout())
+}
+
+/// The shipped game with the strict adviser.
+pub fn kitchen_game(seed: u64) -> KitchenGame {
+    KitchenGame::new(
+        Arc::new(game()),
+        Arc::new(strategy()),
+        Arc::new(layout()),
+        AdviserMode::NextMove,
+        seed,
+    )
+}
@@ -0,0 +1,142 @@
+//! The robot delivers by walking to its delivery station on the map, and the task starts over
+//! after every burger.
+
+mod common;
+
+use std::{collections::HashMap, sync::Arc};
+
+use common::{asset, kitchen_game, layout};
+use serde_json::{json, Value};
+use study_game_logic::{
+    delivery_move, validate, Agent, KitchenGame, NextMove, Position, Strategy, SynthGame,
+};
+use study_shared_types::AdviserMode;
+
+const ALL_MOVES: [NextMove; 6] = [
+    NextMove::Idle,
+    NextMove::Interact,
+    NextMove::Up,
+    NextMove::Down,
+    NextMove::Left,
+    NextMove::Right,
+];
+
+#[test]
+fn delivery_follows_the_floor() {
+    let layout = layout();
+    // the robot delivers at [2, 0], which is only reached over [2, 1]
+    let from = |x, y| delivery_move(&layout, Agent::Robot, Position { x, y }, false, &ALL_MOVES);
+    assert_eq!(from(0, 1), Some(NextMove::Right));
+    assert_eq!(from(4, 1), Some(NextMove::Left));
+    assert_eq!(from(2, 1), Some(NextMove::Down));
+    assert_eq!(from(2, 0), Some(NextMove::Interact));
+    // coming back from a counter first
+    assert_eq!(
+        delivery_move(
+            &layout,
+            Agent::Robot,
+            Position { x: 0, y: 1 },
+            true,
+            &ALL_MOVES
+        ),
+        Some(NextMove::Interact)
+    );
+    // nothing gets the robot closer
+    assert_eq!(
+        delivery_move(
+            &layout,
+            Agent::Robot,
+            Position { x: 0, y: 1 },
+            false,
+            &[NextMove::Idle, NextMove::Left]
+        ),
+        None
+    );
+}
+
+#[test]
+fn robot_delivers_repeatedly() {
+    let mut game = kitchen_game(7);
+    let mut burgers = 0;
+    for _ in 0..400 {
+        let advice = game.advisers().next_move;
+        let outcome = game.step(advice);
+        assert!(!outcome.violated);
+        assert!(
+            game.strategy_error().is_none(),
+            "{:?}",
+            game.strategy_error()
+        );
+        if outcome.robot_burger {
+            burgers += 1;
+        }
+    }
+    assert!(burgers > 1, "the robot delivered {} burgers", burgers);
+}
+
+/// The shipped game and strategy with every state renamed on its own, so there is no state to
+/// restart the task in where the robot delivered.
+fn without_restart_states() -> (SynthGame, Strategy) {
+    let mut names: HashMap<Value, Value> = HashMap::new();
+    let mut rename = |state: &mut Value| {
+        let count = names.len();
+        *state = names
+            .entry(state.clone())
+            .or_insert_with(|| json!([format!("s{}", count), format!("t{}", count), ""]))
+            .clone();
+    };
+
+    let mut game: Value = serde_json::from_str(&asset("game.json.game")).unwrap();
+    rename(&mut game["graph"]["init"]);
+    for state in game["graph"]["acc"].as_array_mut().unwrap() {
+        rename(state);
+    }
+    for node in game["nodes"].as_array_mut().unwrap() {
+        rename(&mut node["id"]);
+    }
+    for link in game["links"].as_array_mut().unwrap() {
+        rename(&mut link["source"]);
+        rename(&mut link["target"]);
+    }
+
+    let mut strategy: Value = serde_json::from_str(&asset("strat.json.strat")).unwrap();
+    for key in ["strat", "safety_adv", "fairness_adv"] {
+        for entry in strategy[key].as_array_mut().unwrap() {
+            rename(&mut entry[0]);
+        }
+    }
+    (
+        serde_json::from_value(game).unwrap(),
+        Strategy::from_json(&strategy.to_string()).unwrap(),
+    )
+}
+
+#[test]
+fn robot_starts_over_at_its_start_without_restart_states() {
+    let ((synth_game, strategy), layout) = (without_restart_states(), layout());
+    let errors = validate(&synth_game, &strategy, &layout);
+    assert!(errors.is_empty(), "{:#?}", errors);
+
+    let mut game = KitchenGame::new(
+        Arc::new(synth_game),
+        Arc::new(strategy),
+        Arc::new(layout),
+        AdviserMode::NextMove,
+        7,
+    );
+    let mut burgers = 0;
+    for _ in 0..400 {
+        let outcome = game.step(game.advisers().next_move);
+        assert!(!outcome.violated);
+        assert!(game.strategy_error().is_none());
+        if outcome.robot_burger {
+            burgers += 1;
+        }
+
+        // the robot is where the game state puts it, also after starting over
+        let (pos, interacting) = game.robot_locations().get(&outcome.game_state).unwrap();
+        assert_eq!(outcome.robot.pos, pos);
+        assert_eq!(outcome.robot.interact.is_active(), interacting);
+    }
+    assert!(burgers > 1, "the robot delivered {} burgers", burgers);
+}
@@ -0,0 +1,90 @@
+//! Which moves are legal follows from the floor tiles of the layout, not from the stations.
+
+mod common;
+
+use common::layout;
+use study_game_logic::{
+    contextual_move, next_pos_from_move, resolve_moves, valid_moves, validate_layout, Actor, Agent,
+    ConsistencyError, Interact, NextMove, Position,
+};
+
+fn pos(x: usize, y: usize) -> Position {
+    Position { x, y }
+}
+
+#[test]
+fn any_floor_tile_can_be_walked_on() {
+    let mut layout = layout();
+    // a nook above the patty station
+    layout.floor.push(pos(0, 4));
+    assert!(validate_layout(&layout).is_empty());
+
+    let moves = valid_moves(&layout, Agent::Human, pos(0, 3), &Interact::No);
+    assert_eq!(
+        moves,
+        [
+            NextMove::Idle,
+            NextMove::Interact,
+            NextMove::Up,
+            NextMove::Right
+        ]
+    );
+    // no station here, and the delivery counter to the right is no floor
+    let moves = valid_moves(&layout, Agent::Human, pos(0, 4), &Interact::No);
+    assert_eq!(moves, [NextMove::Idle, NextMove::Down]);
+}
+
+#[test]
+fn walking_into_the_counter_interacts() {
+    let mut layout = layout();
+    layout.floor.push(pos(0, 4));
+
+    let interact = |at, pressed| contextual_move(&layout, Agent::Human, at, &Interact::No, pressed);
+    assert_eq!(interact(pos(0, 3), NextMove::Down), NextMove::Interact);
+    assert_eq!(interact(pos(0, 3), NextMove::Up), NextMove::Up);
+    assert_eq!(interact(pos(0, 4), NextMove::Down), NextMove::Down);
+    assert_eq!(interact(pos(2, 4), NextMove::Left), NextMove::Interact);
+
+    // reaching over the counter, every key comes back
+    let reaching = Interact::In(pos(0, 2));
+    let pressed = contextual_move(&layout, Agent::Human, pos(0, 3), &reaching, NextMove::Up);
+    assert_eq!(pressed, NextMove::Interact);
+}
+
+#[test]
+fn moves_off_the_grid_are_blocked() {
+    assert_eq!(next_pos_from_move(&pos(0, 1), NextMove::Left), pos(0, 1));
+    assert_eq!(next_pos_from_move(&pos(2, 0), NextMove::Down), pos(2, 0));
+    assert_eq!(next_pos_from_move(&pos(1, 1), NextMove::Left), pos(0, 1));
+
+    // the robot starts on the bottom border and the human is next to the left one
+    let layout = layout();
+    let mut human = Actor::new(pos(0, 3));
+    let mut robot = Actor::new(layout.start(Agent::Robot));
+    assert_eq!(robot.pos.y, 0);
+    resolve_moves(
+        &layout,
+        &mut human,
+        &mut robot,
+        NextMove::Left,
+        NextMove::Down,
+    );
+    assert_eq!(human.pos, pos(0, 3));
+    assert_eq!(robot.pos, layout.start(Agent::Robot));
+}
+
+#[test]
+fn blocked_stations_are_reported() {
+    let mut layout = layout();
+    layout.floor.retain(|&p| p != pos(2, 3));
+
+    let errors = validate_layout(&layout);
+    assert!(errors.contains(&ConsistencyError::NotFloor(pos(2, 3))));
+    for x in [0, 1, 3, 4] {
+        assert!(errors.contains(&ConsistencyError::StationUnreachable {
+            agent: Agent::Human,
+            pos: pos(x, 3),
+        }));
+    }
+    assert_eq!(errors.len(), 5, "{:#?}", errors);
+}
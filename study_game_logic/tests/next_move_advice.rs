@@ -0,0 +1,104 @@
+//! The strict adviser's move is computed from where the human is and what they carry, so it
+//! stays valid and safe after the human strays or the game is reset.
+
+mod common;
+
+use common::{kitchen_game, layout};
+use study_game_logic::{
+    next_move_advice, ActiveAdvisers, Actor, Agent, KitchenGame, NextMove, Position,
+};
+
+/// Follows the advice, returns the burgers the human made.
+fn follow(game: &mut KitchenGame, steps: usize) -> u32 {
+    let mut burgers = 0;
+    for _ in 0..steps {
+        let advice = game.advisers().next_move;
+        assert!(game.valid_human_moves().contains(&advice), "{:?}", advice);
+        let outcome = game.step(advice);
+        assert!(!outcome.violated);
+        assert!(game.strategy_error().is_none());
+        if outcome.human_burger {
+            burgers += 1;
+        }
+    }
+    burgers
+}
+
+#[test]
+fn advice_makes_burgers() {
+    let mut game = kitchen_game(1);
+    assert!(follow(&mut game, 300) > 0);
+}
+
+#[test]
+fn advice_recovers_after_a_reset() {
+    let mut game = kitchen_game(2);
+    follow(&mut game, 7);
+    // anything but the advice resets the game halfway through a burger
+    let stray = game
+        .valid_human_moves()
+        .into_iter()
+        .find(|&m| m != game.advisers().next_move)
+        .unwrap();
+    assert!(game.step(stray).violated);
+    assert!(follow(&mut game, 300) > 0);
+}
+
+#[test]
+fn forbidden_interaction_is_not_advised() {
+    let layout = layout();
+    let guard_ap: Vec<String> = ["lettuce_h", "patty_h", "tomato_h", "buns_h", "ketchup_h"]
+        .iter()
+        .map(|ap| ap.to_string())
+        .collect();
+    // in front of the buns, which the recipe asks for
+    let mut human = Actor::new(layout.start(Agent::Human));
+    human.pos = Position { x: 1, y: 3 };
+
+    let mut advisers = ActiveAdvisers::default();
+    let advice = next_move_advice(&layout, &human, &advisers, &guard_ap);
+    assert_eq!(advice, NextMove::Interact);
+
+    // the buns are the last ingredient left
+    human.progress.taken = ["patty", "lettuce", "tomato", "sauce"]
+        .iter()
+        .map(|i| i.to_string())
+        .collect();
+    advisers.safety.push(vec!["XXX1X".to_owned()]);
+    let advice = next_move_advice(&layout, &human, &advisers, &guard_ap);
+    assert_eq!(advice, NextMove::Idle);
+
+    // the robot waits for help with the sauce
+    advisers.fairness.push(vec!["XXXX1".to_owned()]);
+    let advice = next_move_advice(&layout, &human, &advisers, &guard_ap);
+    assert_eq!(advice, NextMove::Right);
+}
+
+#[test]
+fn advice_walks_past_forbidden_stations() {
+    let layout = layout();
+    let guard_ap: Vec<String> = ["lettuce_h", "patty_h", "tomato_h", "buns_h", "ketchup_h"]
+        .iter()
+        .map(|ap| ap.to_string())
+        .collect();
+    // the buns are taken, the patty to the left and the tomato to the right are closest
+    let mut human = Actor::new(layout.start(Agent::Human));
+    human.pos = Position { x: 1, y: 3 };
+    human.progress.taken.push("buns".to_owned());
+
+    let mut advisers = ActiveAdvisers::default();
+    let advice = next_move_advice(&layout, &human, &advisers, &guard_ap);
+    assert_eq!(advice, NextMove::Left);
+
+    // both first moves are safe, but the walk would end in a forbidden interaction
+    advisers
+        .safety
+        .push(vec!["X1XXX".to_owned(), "XX1XX".to_owned()]);
+    let advice = next_move_advice(&layout, &human, &advisers, &guard_ap);
+    assert_eq!(advice, NextMove::Right);
+
+    // standing at the tomato on the way, the human goes on to the sauce or the lettuce
+    human.pos = Position { x: 2, y: 3 };
+    let advice = next_move_advice(&layout, &human, &advisers, &guard_ap);
+    assert_eq!(advice, NextMove::Right);
+}
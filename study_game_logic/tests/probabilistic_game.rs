@@ -0,0 +1,179 @@
+//! A game in which the robot's grasps fail half of the time. The robot has to be shown where
+//! the drawn outcome put it, not where the strategy wanted it to go.
+
+mod common;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use common::{asset, layout, strategy};
+
+use serde_json::{json, Value};
+use study_game_logic::{synthesize, validate, validate_game, KitchenGame, NextMove, SynthGame};
+use study_shared_types::AdviserMode;
+
+/// Every grasp at a counter fails with probability 0.5, the robot stays in front of it.
+fn failing_grasps() -> SynthGame {
+    serde_json::from_value(failing_grasps_json()).unwrap()
+}
+
+fn failing_grasps_json() -> Value {
+    let mut json: Value = serde_json::from_str(&asset("game.json.game")).unwrap();
+    let nodes: HashSet<Value> = json["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n["id"].clone())
+        .collect();
+
+    let mut failures = Vec::new();
+    for link in json["links"].as_array_mut().unwrap() {
+        let source = link["source"].clone();
+        let robot = source[0].as_str().unwrap();
+        if link["prob"].is_null() || !robot.ends_with("_interact") || robot.contains("i_") {
+            continue;
+        }
+        let failed = json!([robot.trim_end_matches("_interact"), source[1], ""]);
+        if nodes.contains(&failed) {
+            link["prob"] = json!(0.5);
+            failures.push(json!({"prob": 0.5, "source": source, "target": failed}));
+        }
+    }
+    assert!(!failures.is_empty());
+    json["links"].as_array_mut().unwrap().extend(failures);
+    json
+}
+
+/// The same game with the robot and task parts of the states renamed to names that say
+/// nothing about where the robot is.
+fn renamed_failing_grasps() -> SynthGame {
+    let mut names: [HashMap<String, String>; 2] = Default::default();
+    let mut rename = |state: &mut Value| {
+        for (part, names) in names.iter_mut().enumerate() {
+            let name = state[part].as_str().unwrap().to_owned();
+            let count = names.len();
+            let renamed = names
+                .entry(name)
+                .or_insert_with(|| format!("{}{}", ["r", "t"][part], count));
+            state[part] = json!(renamed);
+        }
+    };
+
+    let mut json = failing_grasps_json();
+    rename(&mut json["graph"]["init"]);
+    for state in json["graph"]["acc"].as_array_mut().unwrap() {
+        rename(state);
+    }
+    for node in json["nodes"].as_array_mut().unwrap() {
+        rename(&mut node["id"]);
+    }
+    for link in json["links"].as_array_mut().unwrap() {
+        rename(&mut link["source"]);
+        rename(&mut link["target"]);
+    }
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn failing_grasps_are_valid() {
+    let errors = validate_game(&failing_grasps(), &layout());
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn failing_grasps_can_be_synthesized() {
+    let (game, layout) = (failing_grasps(), layout());
+    let strategy = synthesize(&game, &layout).unwrap();
+    let errors = validate(&game, &strategy, &layout);
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn robot_follows_sampled_outcome() {
+    let synth_game = Arc::new(failing_grasps());
+    let strategy = Arc::new(strategy());
+    let layout = Arc::new(layout());
+    let mut game = KitchenGame::new(synth_game, strategy, layout, AdviserMode::NextMove, 42);
+
+    let (mut failed, mut succeeded) = (0, 0);
+    for _ in 0..500 {
+        let outcome = game.step(game.input_move(game.advisers().next_move));
+        assert!(!outcome.violated);
+
+        let (pos, interacting) = game.robot_locations().get(&outcome.game_state).unwrap();
+        assert_eq!(outcome.robot.pos, pos);
+        assert_eq!(outcome.robot.interact.is_active(), interacting);
+
+        if let Some(sampled) = outcome.sampled {
+            assert_eq!(sampled.robot_move, NextMove::Interact);
+            if outcome.robot_move == NextMove::Idle {
+                failed += 1;
+            } else {
+                succeeded += 1;
+            }
+        }
+    }
+    assert!(failed > 0 && succeeded > 0, "{} {}", failed, succeeded);
+}
+
+#[test]
+fn robot_follows_sampled_outcome_of_renamed_states() {
+    let synth_game = renamed_failing_grasps();
+    let layout = layout();
+    assert!(!synth_game.graph.init.0.starts_with(char::is_numeric));
+    let strategy = synthesize(&synth_game, &layout).unwrap();
+    let mut game = KitchenGame::new(
+        Arc::new(synth_game),
+        Arc::new(strategy),
+        Arc::new(layout),
+        AdviserMode::NextMove,
+        7,
+    );
+    assert!(game.robot_locations().conflicts().is_empty());
+
+    let (mut failed, mut succeeded) = (0, 0);
+    for _ in 0..500 {
+        let outcome = game.step(game.input_move(game.advisers().next_move));
+        assert!(!outcome.violated);
+
+        let (pos, interacting) = game.robot_locations().get(&outcome.game_state).unwrap();
+        assert_eq!(outcome.robot.pos, pos);
+        assert_eq!(outcome.robot.interact.is_active(), interacting);
+
+        if let Some(sampled) = outcome.sampled {
+            assert_eq!(sampled.robot_move, NextMove::Interact);
+            if outcome.robot_move == NextMove::Idle {
+                failed += 1;
+            } else {
+                succeeded += 1;
+            }
+        }
+    }
+    assert!(failed > 0 && succeeded > 0, "{} {}", failed, succeeded);
+}
+
+#[test]
+fn same_seed_same_game() {
+    let synth_game = Arc::new(failing_grasps());
+    let strategy = Arc::new(strategy());
+    let layout = Arc::new(layout());
+    let play = |seed| {
+        let mut game = KitchenGame::new(
+            Arc::clone(&synth_game),
+            Arc::clone(&strategy),
+            Arc::clone(&layout),
+            AdviserMode::NextMove,
+            seed,
+        );
+        (0..200)
+            .map(|_| {
+                game.step(game.input_move(game.advisers().next_move))
+                    .game_state
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(play(1), play(1));
+    assert_ne!(play(1), play(2));
+}
@@ -0,0 +1,60 @@
+//! Atomic propositions are bound to stations by the layout, so new ones need no code.
+
+mod common;
+
+use common::{game, layout};
+use study_game_logic::{
+    obs_from_pos, validate_game, Agent, ConsistencyError, Interact, Position, Proposition,
+    StationKind,
+};
+
+#[test]
+fn new_proposition_is_observed() {
+    let mut layout = layout();
+    layout.propositions.push(Proposition {
+        name: "delivery_h".to_owned(),
+        agent: Agent::Human,
+        station: StationKind::Delivery,
+        sprite: None,
+        label: "delivery".to_owned(),
+    });
+    let aps = ["ketchup_h".to_owned(), "delivery_h".to_owned()];
+
+    // the human delivers at [2, 4] and takes the sauce at [3, 3]
+    let delivery = Position { x: 2, y: 4 };
+    let sauce = Position { x: 3, y: 3 };
+    let interacting = |pos| Interact::In(layout.station_at(Agent::Human, pos).unwrap().counter);
+    assert_eq!(
+        obs_from_pos(&layout, delivery, &interacting(delivery), &aps),
+        "01"
+    );
+    assert_eq!(
+        obs_from_pos(&layout, sauce, &interacting(sauce), &aps),
+        "10"
+    );
+    assert_eq!(obs_from_pos(&layout, delivery, &Interact::No, &aps), "00");
+}
+
+#[test]
+fn unknown_propositions_are_reported() {
+    let game = game();
+    let mut layout = layout();
+    layout.propositions.retain(|p| p.name != "tomato_r");
+    // a robot proposition does not hold for the human
+    let lettuce = layout
+        .propositions
+        .iter_mut()
+        .find(|p| p.name == "lettuce_h")
+        .unwrap();
+    lettuce.agent = Agent::Robot;
+
+    let errors = validate_game(&game, &layout);
+    assert!(errors.contains(&ConsistencyError::UnknownProposition {
+        agent: Agent::Robot,
+        name: "tomato_r".to_owned(),
+    }));
+    assert!(errors.contains(&ConsistencyError::UnknownProposition {
+        agent: Agent::Human,
+        name: "lettuce_h".to_owned(),
+    }));
+}
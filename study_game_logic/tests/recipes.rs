@@ -0,0 +1,89 @@
+//! Recipes come with the layout, each agent collects what their own recipe asks for.
+
+mod common;
+
+use common::layout;
+use study_game_logic::{
+    resolve_moves, validate_layout, Actor, Agent, ConsistencyError, KitchenLayout, NextMove, Recipe,
+};
+
+fn recipe(ingredients: &[&str], order: &[[&str; 2]]) -> Recipe {
+    Recipe {
+        ingredients: ingredients.iter().map(|i| i.to_string()).collect(),
+        order: order
+            .iter()
+            .map(|[before, after]| [before.to_string(), after.to_string()])
+            .collect(),
+    }
+}
+
+/// Reaches over the counter and back, returns whether the human delivered a burger.
+fn interact(layout: &KitchenLayout, human: &mut Actor, robot: &mut Actor) -> bool {
+    let (delivered, _) = resolve_moves(layout, human, robot, NextMove::Interact, NextMove::Idle);
+    resolve_moves(layout, human, robot, NextMove::Interact, NextMove::Idle);
+    delivered
+}
+
+/// Puts the human in front of a counter and interacts with it, the robot idles at its start.
+fn interact_at(layout: &KitchenLayout, human: &mut Actor, robot: &mut Actor, x: usize) {
+    human.pos = layout
+        .stations_of(Agent::Human)
+        .find(|s| s.counter.x == x && s.counter.y == 2)
+        .unwrap()
+        .pos;
+    interact(layout, human, robot);
+}
+
+#[test]
+fn ingredients_wait_for_their_order() {
+    let mut layout = layout();
+    layout.recipes.human = recipe(&["buns", "patty"], &[["buns", "patty"]]);
+    assert!(validate_layout(&layout).is_empty());
+    let mut human = Actor::new(layout.start(Agent::Human));
+    let mut robot = Actor::new(layout.start(Agent::Robot));
+
+    // patty [0, 2] before buns [1, 2] is not taken
+    interact_at(&layout, &mut human, &mut robot, 0);
+    assert!(human.progress.taken.is_empty());
+    interact_at(&layout, &mut human, &mut robot, 1);
+    interact_at(&layout, &mut human, &mut robot, 0);
+    assert_eq!(human.progress.taken, ["buns", "patty"]);
+    assert!(human.progress.ready(layout.recipe(Agent::Human)));
+
+    // ingredients the recipe does not ask for are left on the counter
+    interact_at(&layout, &mut human, &mut robot, 4);
+    assert_eq!(human.progress.taken, ["buns", "patty"]);
+}
+
+#[test]
+fn recipes_are_per_agent() {
+    let mut layout = layout();
+    layout.recipes.human = recipe(&["tomato"], &[]);
+    let mut human = Actor::new(layout.start(Agent::Human));
+    let mut robot = Actor::new(layout.start(Agent::Robot));
+
+    interact_at(&layout, &mut human, &mut robot, 2);
+    human.pos = layout.start(Agent::Human);
+    assert!(interact(&layout, &mut human, &mut robot));
+    assert_eq!(human.progress.assembled, 1);
+    assert!(!robot.progress.ready(layout.recipe(Agent::Robot)));
+}
+
+#[test]
+fn broken_recipes_are_reported() {
+    let mut layout = layout();
+    layout.recipes.human = recipe(&["buns", "patty"], &[["buns", "patty"], ["patty", "buns"]]);
+    layout.recipes.robot = recipe(&["buns", "cheese"], &[["onion", "buns"]]);
+
+    let errors = validate_layout(&layout);
+    assert!(errors.contains(&ConsistencyError::RecipeOrderCycle(Agent::Human)));
+    assert!(errors.contains(&ConsistencyError::NoStationForIngredient {
+        agent: Agent::Robot,
+        ingredient: "cheese".to_owned(),
+    }));
+    assert!(errors.contains(&ConsistencyError::UnknownOrderIngredient {
+        agent: Agent::Robot,
+        ingredient: "onion".to_owned(),
+    }));
+    assert_eq!(errors.len(), 3, "{:#?}", errors);
+}
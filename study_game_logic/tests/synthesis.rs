@@ -0,0 +1,58 @@
+//! The strategy and advisers synthesized in Rust can replace the ones of the synthesis tool.
+
+mod common;
+
+use std::{collections::HashMap, sync::Arc};
+
+use common::{asset, shipped};
+
+use study_game_logic::{synthesize, validate, KitchenGame, Strategy, SynthGame, SynthesisError};
+use study_shared_types::AdviserMode;
+
+#[test]
+fn synthesized_strategy_is_valid() {
+    let (game, shipped, layout) = shipped();
+    let strategy = synthesize(&game, &layout).unwrap();
+    let errors = validate(&game, &strategy, &layout);
+    assert!(errors.is_empty(), "{:#?}", errors);
+
+    // the same assumptions the synthesis tool made
+    let safety = |s: &Strategy| s.safety_adv.iter().cloned().collect::<HashMap<_, _>>();
+    assert_eq!(safety(&strategy), safety(&shipped));
+    assert_eq!(strategy.fairness_adv.len(), shipped.fairness_adv.len());
+}
+
+#[test]
+fn synthesized_strategy_makes_burgers() {
+    let (game, _, layout) = shipped();
+    let strategy = synthesize(&game, &layout).unwrap();
+    let mut game = KitchenGame::new(
+        Arc::new(game),
+        Arc::new(strategy),
+        Arc::new(layout),
+        AdviserMode::NextMove,
+        3,
+    );
+    let (mut human, mut robot) = (0, 0);
+    for _ in 0..300 {
+        let outcome = game.step(game.advisers().next_move);
+        assert!(!outcome.violated);
+        assert!(game.strategy_error().is_none());
+        human += outcome.human_burger as u32;
+        robot += outcome.robot_burger as u32;
+    }
+    assert!(human > 0 && robot > 0, "{} {}", human, robot);
+}
+
+#[test]
+fn unreachable_task_is_reported() {
+    let (game, _, layout) = shipped();
+    let mut json: serde_json::Value = serde_json::from_str(&asset("game.json.game")).unwrap();
+    json["graph"]["acc"] = serde_json::json!([]);
+    let without_goal: SynthGame = serde_json::from_value(json).unwrap();
+
+    assert_eq!(
+        synthesize(&without_goal, &layout).unwrap_err(),
+        SynthesisError::InitLosing(game.graph.init)
+    );
+}
@@ -0,0 +1,78 @@
+//! The shipped assets have to pass the same validation the frontend runs after loading.
+
+mod common;
+
+use common::{asset, shipped};
+use study_game_logic::{
+    validate, validate_game, Agent, ConsistencyError, Position, StationKind, Strategy, SynthGame,
+};
+
+fn sauce() -> StationKind {
+    StationKind::Ingredient("sauce".to_owned())
+}
+
+#[test]
+fn shipped_assets_are_consistent() {
+    let (game, strategy, layout) = shipped();
+    let errors = validate(&game, &strategy, &layout);
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn mismatches_are_all_reported() {
+    let (game, _, mut layout) = shipped();
+    let mut json: serde_json::Value = serde_json::from_str(&asset("strat.json.strat")).unwrap();
+    json["guard_ap"].as_array_mut().unwrap().reverse();
+    let strat = json["strat"].as_array_mut().unwrap();
+    strat.push(serde_json::json!([["99", "1", ""], "up"]));
+    strat.retain(|entry| entry[0] != serde_json::json!(["20", "1", ""]));
+    let strategy: Strategy = serde_json::from_value(json).unwrap();
+    layout
+        .stations
+        .retain(|s| !(s.kind == sauce() && s.agent == Agent::Human));
+
+    let errors = validate(&game, &strategy, &layout);
+    assert!(errors
+        .iter()
+        .any(|e| matches!(e, ConsistencyError::GuardApMismatch { .. })));
+    assert!(errors.contains(&ConsistencyError::StrategyStateMissing((
+        "99".to_owned(),
+        "1".to_owned(),
+        String::new()
+    ))));
+    assert!(errors.contains(&ConsistencyError::NoStrategyMove((
+        "20".to_owned(),
+        "1".to_owned(),
+        String::new()
+    ))));
+    assert!(
+        errors.contains(&ConsistencyError::PropositionWithoutStation {
+            name: "ketchup_h".to_owned(),
+            agent: Agent::Human,
+            station: sauce(),
+        })
+    );
+    assert!(errors.contains(&ConsistencyError::HelperMissing {
+        kind: sauce(),
+        counter: Position { x: 3, y: 2 },
+    }));
+}
+
+#[test]
+fn robots_in_two_places_are_reported() {
+    let (_, _, layout) = shipped();
+    let mut json: serde_json::Value = serde_json::from_str(&asset("game.json.game")).unwrap();
+    // walking up from the delivery station ends at the lettuce instead
+    let far = serde_json::json!(["41", "1", ""]);
+    for link in json["links"].as_array_mut().unwrap() {
+        if link["source"][0] == "20_up" && link["prob"].is_number() {
+            link["target"] = far.clone();
+        }
+    }
+    let game: SynthGame = serde_json::from_value(json).unwrap();
+
+    let errors = validate_game(&game, &layout);
+    assert!(errors.contains(&ConsistencyError::RobotLocationConflict(
+        serde_json::from_value(far).unwrap()
+    )));
+}
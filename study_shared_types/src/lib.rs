@@ -1,39 +1,183 @@
+use std::{error::Error, fmt::Display, time::Duration};
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Schema version of `ResultSubmission` produced by this build.
+pub const RESULT_SCHEMA_VERSION: u32 = 2;
+pub const STUDY_ID: &str = "human-adviser-study";
+
+/// Time one step takes to animate, i.e. the fastest a participant can move.
+pub const ANIM_DURATION: Duration = Duration::from_millis(400);
+pub const GAME_DURATION: Duration = Duration::from_secs(120);
+/// Upper bound on the steps of one game.
+pub const MAX_STEPS: u32 = (GAME_DURATION.as_millis() / ANIM_DURATION.as_millis()) as u32;
+/// Fewest steps between two deliveries in the shipped kitchen: walking down to the counters,
+/// along all five of them and back up takes 8 moves, and each of the five ingredients and the
+/// delivery needs one step to start and one to end the interaction. Other layouts can be faster,
+/// so results above this are only flagged.
+pub const MIN_STEPS_PER_BURGER: u32 = 8 + 6 * 2;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GameResults {
     pub participant_id: i32,
-    pub adviser_mode: u32,
+    pub session_token: String,
+    pub adviser_mode: AdviserMode,
     pub steps_taken: u32,
     pub safety_violated: u32,
     pub human_burgers: u32,
     pub robot_burgers: u32,
 }
 
-impl GameResults {
-    pub fn to_json(&self) -> String {
-        format!("{{\"participant_id\": {}, \"adviser_mode\": {}, \"steps_taken\": {}, \"safety_violated\": {}, \"human_burgers\": {}, \"robot_burgers\": {}}}", self.participant_id, self.adviser_mode, self.steps_taken, self.safety_violated, self.human_burgers, self.robot_burgers)
+/// Versioned envelope around the results that the frontend posts to the backend.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResultSubmission {
+    pub schema_version: u32,
+    pub study_id: String,
+    /// git hash (or other identifier) of the frontend build
+    pub client_build: String,
+    /// unix milliseconds, as reported by the client
+    pub started_at: u64,
+    /// unix milliseconds, as reported by the client
+    pub submitted_at: u64,
+    pub result: GameResults,
+}
+
+/// Schema version 1: bare results with the adviser mode as a number.
+#[derive(Debug, Deserialize)]
+struct GameResultsV1 {
+    participant_id: i32,
+    #[serde(default)]
+    session_token: String,
+    adviser_mode: u32,
+    steps_taken: u32,
+    safety_violated: u32,
+    human_burgers: u32,
+    robot_burgers: u32,
+}
+
+#[derive(Debug)]
+pub enum SubmissionError {
+    Malformed(String),
+    UnsupportedVersion(u32),
+    InvalidAdviserMode(u32),
+}
+
+impl Display for SubmissionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmissionError::Malformed(e) => write!(f, "malformed submission: {}", e),
+            SubmissionError::UnsupportedVersion(v) => {
+                write!(f, "unsupported schema version {}", v)
+            }
+            SubmissionError::InvalidAdviserMode(m) => write!(f, "invalid adviser mode {}", m),
+        }
+    }
+}
+
+impl Error for SubmissionError {}
+
+impl From<serde_json::Error> for SubmissionError {
+    fn from(e: serde_json::Error) -> Self {
+        SubmissionError::Malformed(e.to_string())
+    }
+}
+
+impl ResultSubmission {
+    pub fn new(
+        result: GameResults,
+        client_build: &str,
+        started_at: u64,
+        submitted_at: u64,
+    ) -> Self {
+        ResultSubmission {
+            schema_version: RESULT_SCHEMA_VERSION,
+            study_id: STUDY_ID.to_owned(),
+            client_build: client_build.to_owned(),
+            started_at,
+            submitted_at,
+            result,
+        }
+    }
+
+    /// Parses any schema version we know about. Submissions without a `schema_version` are
+    /// version 1, which lacks all envelope fields.
+    pub fn parse(json: &str) -> Result<Self, SubmissionError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let version = match value.get("schema_version") {
+            None => 1,
+            Some(v) => v.as_u64().ok_or_else(|| {
+                SubmissionError::Malformed("schema_version is not a number".into())
+            })? as u32,
+        };
+
+        match version {
+            1 => {
+                let v1: GameResultsV1 = serde_json::from_value(value)?;
+                let adviser_mode = AdviserMode::from_num(v1.adviser_mode)
+                    .ok_or(SubmissionError::InvalidAdviserMode(v1.adviser_mode))?;
+                Ok(ResultSubmission {
+                    schema_version: 1,
+                    study_id: STUDY_ID.to_owned(),
+                    client_build: String::new(),
+                    started_at: 0,
+                    submitted_at: 0,
+                    result: GameResults {
+                        participant_id: v1.participant_id,
+                        session_token: v1.session_token,
+                        adviser_mode,
+                        steps_taken: v1.steps_taken,
+                        safety_violated: v1.safety_violated,
+                        human_burgers: v1.human_burgers,
+                        robot_burgers: v1.robot_burgers,
+                    },
+                })
+            }
+            RESULT_SCHEMA_VERSION => Ok(serde_json::from_value(value)?),
+            v => Err(SubmissionError::UnsupportedVersion(v)),
+        }
     }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionInfo {
+    pub participant_id: i32,
+    pub session_token: String,
+    pub adviser_mode: AdviserMode,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum AdviserMode {
     LeastLimiting,
     NextMove,
     None,
 }
 
-impl ToString for AdviserMode {
-    fn to_string(&self) -> String {
+impl Display for AdviserMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            AdviserMode::LeastLimiting => "LeastLimiting".to_string(),
-            AdviserMode::NextMove => "NextMove".to_string(),
-            AdviserMode::None => "None".to_string(),
+            AdviserMode::LeastLimiting => write!(f, "LeastLimiting"),
+            AdviserMode::NextMove => write!(f, "NextMove"),
+            AdviserMode::None => write!(f, "None"),
         }
     }
 }
 
 impl AdviserMode {
+    pub const ALL: [AdviserMode; 3] = [
+        AdviserMode::LeastLimiting,
+        AdviserMode::NextMove,
+        AdviserMode::None,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "LeastLimiting" => Some(AdviserMode::LeastLimiting),
+            "NextMove" => Some(AdviserMode::NextMove),
+            "None" => Some(AdviserMode::None),
+            _ => None,
+        }
+    }
+
     pub fn to_num(&self) -> u32 {
         match self {
             AdviserMode::LeastLimiting => 0,
@@ -42,12 +186,58 @@ impl AdviserMode {
         }
     }
 
-    pub fn from_num(n: u32) -> Self {
+    pub fn from_num(n: u32) -> Option<Self> {
         match n {
-            0 => AdviserMode::LeastLimiting,
-            1 => AdviserMode::NextMove,
-            2 => AdviserMode::None,
-            _ => panic!("invalid number!"),
+            0 => Some(AdviserMode::LeastLimiting),
+            1 => Some(AdviserMode::NextMove),
+            2 => Some(AdviserMode::None),
+            _ => None,
         }
     }
 }
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum InteractState {
+    No,
+    In,
+    Out,
+    Stay,
+}
+
+impl Display for InteractState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InteractState::No => write!(f, "No"),
+            InteractState::In => write!(f, "In"),
+            InteractState::Out => write!(f, "Out"),
+            InteractState::Stay => write!(f, "Stay"),
+        }
+    }
+}
+
+/// A single resolved step of the study, as seen after both agents moved.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StepEvent {
+    pub step: u32,
+    /// milliseconds since the game timer started
+    pub timestamp_ms: u64,
+    pub human_move: String,
+    pub robot_move: String,
+    pub human_pos: (u32, u32),
+    pub robot_pos: (u32, u32),
+    pub human_interact: InteractState,
+    pub robot_interact: InteractState,
+    pub observation: String,
+    pub game_state: (String, String, String),
+    /// one entry per active adviser, its guards joined by `|`
+    pub safety_guards: Vec<String>,
+    pub fairness_guards: Vec<String>,
+    pub violated: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StepBatch {
+    pub participant_id: i32,
+    pub session_token: String,
+    pub events: Vec<StepEvent>,
+}